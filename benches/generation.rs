@@ -0,0 +1,131 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dungeon_crawler_map::map::{Config, Grid, Map, Path, StepKind, Variants};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const SEED: u64 = 42;
+
+/// The dungeon tileset, configured like the viewer does.
+fn dungeon_config() -> Config {
+    let image = image::open("maps/dungeon/map.png").expect("Failed to open map image!");
+    let variants = vec![
+        Variants {
+            index: 0,
+            weight: 0.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 1,
+            entrance: true,
+            exit: true,
+            ..Default::default()
+        },
+        Variants {
+            index: 2,
+            weight: 2.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 3,
+            weight: 2.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 4,
+            weight: 2.0,
+            ..Default::default()
+        },
+    ];
+
+    Config {
+        image: Some((image, 5)),
+        variants,
+        ..Default::default()
+    }
+}
+
+/// A finished 12x12 map, built with a fixed seed.
+fn built_map(config: &Config) -> Map {
+    let mut map = Map::new(12, 0..usize::MAX);
+    map.build(&mut StdRng::seed_from_u64(SEED), config, false);
+    map
+}
+
+fn load_config(c: &mut Criterion) {
+    let config = dungeon_config();
+    let mut map = Map::new(12, 0..usize::MAX);
+
+    c.bench_function("load_config", |b| b.iter(|| map.load_config(black_box(&config))));
+}
+
+fn generate_map(c: &mut Criterion) {
+    let config = dungeon_config();
+    let mut group = c.benchmark_group("generate_map");
+
+    for size in [8, 16, 32] {
+        let mut map = Map::new(size, 0..usize::MAX);
+        map.load_config(&config);
+
+        // A single attempt from a fixed seed, which runs the same way every time whether it succeeds or not.
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, size| {
+            b.iter(|| {
+                let mut rng = StdRng::seed_from_u64(SEED);
+                let entrance = (size / 2, size / 2);
+                let grid = map.clear(&mut rng, &entrance).unwrap();
+                black_box(map.generate_map(&mut rng, grid, &entrance, None, false).is_ok())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn get_free_neighbors(c: &mut Criterion) {
+    let map = built_map(&dungeon_config());
+
+    // Keep every other row so that most placed tiles border empty cells.
+    let mut grid = map.history.last().unwrap().clone();
+    for y in (0..grid.size).step_by(2) {
+        for x in 0..grid.size {
+            grid.set(&(x, y), None);
+        }
+    }
+
+    c.bench_function("get_free_neighbors", |b| b.iter(|| map.get_free_neighbors(black_box(&grid))));
+}
+
+fn prune(c: &mut Criterion) {
+    let map = built_map(&dungeon_config());
+    let exit = map.history.last().unwrap().get_by_path(&Path::Exit)[0];
+
+    // The grid as it was before pruning, with every cell filled.
+    let path = map.history.steps().iter().rposition(|step| step.kind == StepKind::Path).unwrap();
+    let grid = map.history.get(path);
+
+    c.bench_function("prune", |b| {
+        b.iter(|| {
+            let mut grid: Grid = grid.clone();
+            map.prune(&mut grid, exit);
+            grid
+        })
+    });
+}
+
+fn build(c: &mut Criterion) {
+    let config = dungeon_config();
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+
+    group.bench_function("12x12", |b| {
+        b.iter(|| {
+            let mut map = Map::new(12, 0..usize::MAX);
+            map.build(&mut StdRng::seed_from_u64(SEED), &config, false);
+            map
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, load_config, generate_map, get_free_neighbors, prune, build);
+criterion_main!(benches);
@@ -1,10 +1,15 @@
 use ::rand::thread_rng;
-use macroquad::prelude::*;
-use std::{fs, time::Instant};
-mod map;
+use dungeon_crawler_map::map;
+use macroquad::{prelude::*, Window};
+use std::{env, fs, ops::Range};
+use timeline::Timeline;
+use view::View;
+
+mod timeline;
+mod view;
 
-const DISPLAY_SIZE: f32 = 64.0;
 const TILE_SIZE: f32 = 5.0;
+const PATH_LENGTH: Range<usize> = 20..40;
 
 fn window_conf() -> Conf {
     Conf {
@@ -15,9 +20,35 @@ fn window_conf() -> Conf {
     }
 }
 
-#[macroquad::main(window_conf())]
-async fn main() {
+fn main() {
     let map_name = "dungeon";
+
+    match env::args().nth(1).as_deref() {
+        Some("analyze") => {
+            let analysis = map::Map::new(12, PATH_LENGTH).analyze(&load_config(map_name));
+            println!("{}", analysis);
+            if !analysis.is_valid() {
+                std::process::exit(1);
+            }
+        }
+        Some("overlapping") => {
+            let args: Vec<String> = env::args().skip(2).collect();
+            if args.len() < 2 {
+                println!("Usage: overlapping <sample> <output> [width] [height] [n]");
+                std::process::exit(1);
+            }
+
+            let arg = |index: usize, default: usize| args.get(index).map_or(default, |a| a.parse().expect("Invalid number!"));
+            let sample = image::open(&args[0]).expect("Failed to open sample image!");
+            let mut overlapping = map::Overlapping::new(arg(2, 48), arg(3, 48), arg(4, 3));
+            overlapping.build(&mut thread_rng(), &sample, false);
+            overlapping.history.last().unwrap().save(&args[1]).expect("Failed to save image!");
+        }
+        _ => Window::from_config(window_conf(), run(map_name)),
+    }
+}
+
+fn load_config(map_name: &str) -> map::Config {
     let variants = vec![
         map::Variants {
             index: 0,
@@ -51,10 +82,18 @@ async fn main() {
         .ok()
         .map(|image| (image.decode().expect("Failed to decode map image!"), TILE_SIZE as u32));
 
-    let config = map::Config { image, variants };
+    map::Config {
+        image,
+        variants,
+        ..Default::default()
+    }
+}
+
+async fn run(map_name: &str) {
+    let config = load_config(map_name);
     let mut rng = thread_rng();
-    let mut map = map::Map::new(12, 20..40);
-    map.build(&mut rng, &config, false);
+    let mut map = map::Map::new(12, PATH_LENGTH);
+    map.build(&mut rng, &config, true);
 
     let mut asset_paths: Vec<_> = fs::read_dir(format!("maps/{}/tiles", map_name).as_str())
         .unwrap()
@@ -73,9 +112,15 @@ async fn main() {
         assets.push(texture);
     }
 
-    let mut update_timer = Instant::now();
-    let mut is_playing = false;
-    let mut history_index = 0;
+    let mut timeline = Timeline::new();
+    let mut show_adjacency = false;
+    let mut heatmap = Heatmap::Off;
+    let mut field = Field::Off;
+    let mut selected_variant = None;
+    let mut pins = map::Grid::new(map.size);
+    let mut impossible: Vec<map::Position> = vec![];
+    let mut view = View::new();
+    let mut attempt = map.failed_attempts.len();
 
     loop {
         clear_background(Color::from_rgba(26, 26, 26, 255));
@@ -83,58 +128,375 @@ async fn main() {
         if is_key_pressed(KeyCode::Escape) {
             return;
         }
-        if is_key_pressed(KeyCode::Enter) {
-            history_index += 1;
-            history_index %= map.history.len();
+        if is_key_pressed(KeyCode::A) {
+            show_adjacency = !show_adjacency;
         }
-        if is_key_pressed(KeyCode::Space) {
-            is_playing = !is_playing;
+        if is_key_pressed(KeyCode::H) {
+            heatmap = heatmap.next();
         }
-
-        if is_playing && update_timer.elapsed().as_secs_f32() > 0.05 && history_index < map.history.len() - 1 {
-            history_index += 1;
-            update_timer = Instant::now();
+        if is_key_pressed(KeyCode::D) {
+            field = field.next();
+        }
+        if is_key_pressed(KeyCode::LeftBracket) && attempt > 0 {
+            attempt -= 1;
+            timeline.reset(get_history(&map, attempt).len());
+        }
+        if is_key_pressed(KeyCode::RightBracket) && attempt < map.failed_attempts.len() {
+            attempt += 1;
+            timeline.reset(get_history(&map, attempt).len());
         }
 
-        let half_size = (map.history[history_index].size as i32) / 2;
-        let mut x = -half_size;
-        let mut y = -half_size;
+        let history = get_history(&map, attempt);
+        timeline.update(history.len());
+
+        if show_adjacency {
+            let columns = (map.variants.len() as f32).sqrt().ceil() as usize;
+            view.update(columns);
+            if is_mouse_button_pressed(MouseButton::Left) {
+                selected_variant = view
+                    .get_cell_at(mouse_position(), columns)
+                    .map(|(x, y)| y * columns + x)
+                    .filter(|index| *index < map.variants.len());
+            }
+
+            draw_adjacency(&assets, &view, &map, columns, selected_variant);
+        } else {
+            view.update(pins.size);
+            let hovered = Some(mouse_position())
+                .filter(|mouse| !timeline.contains(*mouse))
+                .and_then(|mouse| view.get_cell_at(mouse, pins.size));
+            if let Some(position) = hovered {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    let current = pins.get(&position).map(|cell| cell.variant);
+                    pins.set(&position, None);
+
+                    // Cycle through the variants that fit the other pins, or all of them if none do.
+                    let mut candidates = map.get_possible_variants(&pins, &position);
+                    if candidates.is_empty() {
+                        candidates = (0..map.variants.len()).collect();
+                    }
+                    let next = current
+                        .and_then(|variant| candidates.iter().position(|v| *v == variant))
+                        .map_or(0, |index| (index + 1) % candidates.len());
+                    pins.set(&position, Some(map::Cell::new(candidates[next])));
+                    impossible = map.impossible_cells(&pins);
+                }
+                if is_mouse_button_pressed(MouseButton::Right) {
+                    pins.set(&position, None);
+                    impossible = map.impossible_cells(&pins);
+                }
+            }
 
-        for tile in map.history[history_index].tiles.iter() {
-            let (nx, ny) = get_xy(x, y);
+            let grid = history.get(timeline.index);
+            for (index, cell) in grid.tiles.iter().enumerate() {
+                let (x, y) = view.get_cell_xy((index % grid.size, index / grid.size), grid.size);
 
-            if let Some(tile) = tile {
-                let rotation = (tile.direction.clone() as u8) as f32 * std::f32::consts::FRAC_PI_2;
-                draw_tile(&assets, tile, nx, ny, rotation);
-            } else {
-                draw_rectangle(nx, ny, DISPLAY_SIZE, DISPLAY_SIZE, BLACK);
+                if let Some(cell) = cell {
+                    draw_tile(&assets, &view, &map.variants[cell.variant], cell.path, x, y);
+                } else {
+                    draw_rectangle(x, y, view.tile_size, view.tile_size, BLACK);
+                }
             }
 
-            x += 1;
-            if x >= half_size {
-                x = -half_size;
-                y += 1;
+            if heatmap != Heatmap::Off && grid.size == map.size {
+                draw_heatmap(&view, &map, history, &grid, heatmap == Heatmap::Labels);
             }
+            if let Some(path) = field.path().filter(|_| grid.size == map.size) {
+                draw_field(&view, &map, &grid, path);
+            }
+            draw_pins(&assets, &view, &map, &pins, &impossible);
+            draw_attempt(&view, &map, attempt, grid.size);
+            timeline.draw(history.steps());
         }
 
         if is_key_pressed(KeyCode::R) {
-            map.build(&mut rng, &config, false);
-            history_index = map.history.len() - 1;
+            pins = map::Grid::new(map.size);
+            impossible.clear();
+            map.constraints.clear();
+            map.entrance = None;
+            map.exit = None;
+            map.path_length = PATH_LENGTH;
+            map.build(&mut rng, &config, true);
+            attempt = map.failed_attempts.len();
+            timeline.reset(map.history.len());
+        }
+        if is_key_pressed(KeyCode::L) {
+            let layout = map::Layout::generate(&mut rng, map.size, 4);
+            map.apply_layout(&layout);
+            map.path_length = 0..usize::MAX;
+            map.build(&mut rng, &config, true);
+            attempt = map.failed_attempts.len();
+            timeline.reset(map.history.len());
+        }
+        if is_key_pressed(KeyCode::G) {
+            // The pins only constrain this regeneration, so that they can still be cycled through afterwards.
+            let pinned_constraints = pins
+                .tiles
+                .iter()
+                .map(|cell| cell.map(|cell| map::Constraint::Variant(map.variants[cell.variant].key())))
+                .collect();
+            let constraints = std::mem::replace(&mut map.constraints, pinned_constraints);
+            impossible = map.impossible_cells(&pins);
+
+            if impossible.is_empty() {
+                map.entrance = None;
+                map.exit = None;
+                map.path_length = 0..usize::MAX;
+
+                // Bounded, so that pins no map fits around are shown as failed instead of freezing the viewer.
+                let rect = map::Rect {
+                    x: 0,
+                    y: 0,
+                    width: map.size,
+                    height: map.size,
+                };
+                let pinned: Vec<map::Position> = (0..pins.tiles.len())
+                    .map(|index| (index % pins.size, index / pins.size))
+                    .filter(|position| pins.get(position).is_some())
+                    .collect();
+                match map.regenerate_region(&map::Grid::new(map.size), &rect, &mut rng) {
+                    Ok((grid, history)) => {
+                        // Pins the exit cannot be reached from are pruned away.
+                        impossible = pinned
+                            .into_iter()
+                            .filter(|position| grid.get(position).map(|cell| cell.variant) != pins.get(position).map(|cell| cell.variant))
+                            .collect();
+                        map.history = history;
+                        map.failed_attempts.clear();
+                        attempt = map.failed_attempts.len();
+                        timeline.reset(map.history.len());
+                    }
+                    Err(map::Failure::Contradiction(position)) | Err(map::Failure::Entrance(position)) => impossible = vec![position],
+                    Err(_) => impossible = pinned,
+                }
+            }
+            map.constraints = constraints;
         }
         next_frame().await
     }
 }
 
-fn get_xy(x: i32, y: i32) -> (f32, f32) {
-    let size = DISPLAY_SIZE;
-    let x = (screen_width() / 2.0 - size / 2.0) + (x as f32 * size);
-    let y = (screen_height() / 2.0 - size / 2.0) + (y as f32 * size);
-    (x, y)
+/// Overlay showing how many variants remain possible in each empty cell.
+#[derive(PartialEq)]
+enum Heatmap {
+    Off,
+    Colors,
+    Labels,
+}
+
+impl Heatmap {
+    fn next(&self) -> Self {
+        match self {
+            Heatmap::Off => Heatmap::Colors,
+            Heatmap::Colors => Heatmap::Labels,
+            Heatmap::Labels => Heatmap::Off,
+        }
+    }
+}
+
+/// Overlay showing how far it is from each cell to the entrance or exit, with arrows pointing the way there.
+#[derive(PartialEq)]
+enum Field {
+    Off,
+    Entrance,
+    Exit,
+}
+
+impl Field {
+    fn next(&self) -> Self {
+        match self {
+            Field::Off => Field::Entrance,
+            Field::Entrance => Field::Exit,
+            Field::Exit => Field::Off,
+        }
+    }
+
+    fn path(&self) -> Option<map::Path> {
+        match self {
+            Field::Off => None,
+            Field::Entrance => Some(map::Path::Entrance),
+            Field::Exit => Some(map::Path::Exit),
+        }
+    }
+}
+
+/// History of the failed attempt at `attempt`, or of the successful one after them.
+fn get_history(map: &map::Map, attempt: usize) -> &map::History {
+    map.failed_attempts.get(attempt).map_or(&map.history, |attempt| {
+        attempt.history.as_ref().expect("The viewer always logs the history!")
+    })
+}
+
+fn get_rotation(tile: &map::Tile) -> f32 {
+    (tile.direction.clone() as u8) as f32 * std::f32::consts::FRAC_PI_2
+}
+
+fn draw_adjacency(assets: &[Texture2D], view: &View, map: &map::Map, columns: usize, selected: Option<usize>) {
+    let compatible: Vec<Vec<usize>> = match selected {
+        Some(selected) => map::Direction::ALL.iter().map(|d| map.compatible_variants(selected, d)).collect(),
+        None => vec![vec![]; 4],
+    };
+
+    let size = view.tile_size;
+    let font_size = size * 0.3;
+    let labels = [
+        ("N", size / 2.0 - font_size / 4.0, font_size * 0.8),
+        ("E", size - font_size * 0.7, size / 2.0 + font_size / 4.0),
+        ("S", size / 2.0 - font_size / 4.0, size - font_size / 5.0),
+        ("W", font_size / 5.0, size / 2.0 + font_size / 4.0),
+    ];
+
+    for (index, variant) in map.variants.iter().enumerate() {
+        let (x, y) = view.get_cell_xy((index % columns, index / columns), columns);
+        draw_tile(assets, view, variant, map::Path::None, x, y);
+
+        if Some(index) == selected {
+            draw_rectangle_lines(x, y, size, size, 4.0, YELLOW);
+        }
+
+        for (variants, (label, lx, ly)) in compatible.iter().zip(labels) {
+            if variants.contains(&index) {
+                draw_text(label, x + lx, y + ly, font_size, GREEN);
+            }
+        }
+    }
+
+    let caption = match selected {
+        Some(selected) => {
+            let variant = &map.variants[selected];
+            format!(
+                "Variant {} (asset {}, {:?}), N/E/S/W mark where a variant may be placed relative to it",
+                selected, variant.asset, variant.direction
+            )
+        }
+        None => "Click a variant to show its compatible neighbors".to_owned(),
+    };
+    draw_text(&caption, 10.0, 24.0, 24.0, WHITE);
+}
+
+fn draw_heatmap(view: &View, map: &map::Map, history: &map::History, grid: &map::Grid, show_labels: bool) {
+    let size = view.tile_size;
+    let max = map.variants.len().max(1) as f32;
+
+    for (index, tile) in grid.tiles.iter().enumerate() {
+        if tile.is_some() {
+            continue;
+        }
+
+        let position = (index % grid.size, index / grid.size);
+        // The map's constraints may have changed since the shown history was generated.
+        let count = map.get_possible_variants_with(grid, &position, &history.constraints).len();
+        let (x, y) = view.get_cell_xy(position, grid.size);
+
+        // Red for contradictions, then from yellow for few candidates to blue for all of them.
+        let color = if count == 0 {
+            Color::new(1.0, 0.0, 0.0, 0.7)
+        } else {
+            let t = (count - 1) as f32 / (max - 1.0).max(1.0);
+            Color::new(1.0 - t, 1.0 - t * 0.6, t, 0.6)
+        };
+        draw_rectangle(x, y, size, size, color);
+
+        if show_labels {
+            let label = count.to_string();
+            let font_size = size * 0.4;
+            let dimensions = measure_text(&label, None, font_size as u16, 1.0);
+            draw_text(
+                &label,
+                x + (size - dimensions.width) / 2.0,
+                y + (size + dimensions.height) / 2.0,
+                font_size,
+                BLACK,
+            );
+        }
+    }
+}
+
+fn draw_field(view: &View, map: &map::Map, grid: &map::Grid, path: map::Path) {
+    let size = view.tile_size;
+    let distances = grid.distances_to(&map.variants, &path);
+    let flow = grid.flow(&map.variants, &grid.get_by_path(&path));
+    let max = distances.iter().flatten().max().copied().unwrap_or(0).max(1) as f32;
+
+    for (index, distance) in distances.iter().enumerate() {
+        let Some(distance) = distance else {
+            continue;
+        };
+        let (x, y) = view.get_cell_xy((index % grid.size, index / grid.size), grid.size);
+
+        // Green next to the source, red for the cells farthest away.
+        let t = *distance as f32 / max;
+        draw_rectangle(x, y, size, size, Color::new(t, 1.0 - t, 0.0, 0.5));
+
+        if let Some(direction) = &flow[index] {
+            let forward = match direction {
+                map::Direction::North => vec2(0.0, -1.0),
+                map::Direction::East => vec2(1.0, 0.0),
+                map::Direction::South => vec2(0.0, 1.0),
+                map::Direction::West => vec2(-1.0, 0.0),
+            } * size;
+            let side = forward.perp() * 0.15;
+            let center = vec2(x, y) + size / 2.0;
+
+            draw_line(
+                center.x,
+                center.y,
+                center.x + forward.x * 0.2,
+                center.y + forward.y * 0.2,
+                size * 0.08,
+                WHITE,
+            );
+            draw_triangle(
+                center + forward * 0.4,
+                center + forward * 0.2 + side,
+                center + forward * 0.2 - side,
+                WHITE,
+            );
+        }
+    }
+}
+
+fn draw_attempt(view: &View, map: &map::Map, attempt: usize, grid_size: usize) {
+    let attempts = map.failed_attempts.len() + 1;
+    let caption = match map.failed_attempts.get(attempt) {
+        Some(failed) => {
+            let position = match failed.failure {
+                map::Failure::Entrance(position) | map::Failure::Contradiction(position) => Some(position),
+                _ => None,
+            };
+            if let Some(position) = position.filter(|_| grid_size == map.size) {
+                let (x, y) = view.get_cell_xy(position, grid_size);
+                draw_rectangle_lines(x, y, view.tile_size, view.tile_size, 4.0, RED);
+            }
+
+            format!("Attempt {}/{} failed: {}", attempt + 1, attempts, failed.failure)
+        }
+        None => format!("Attempt {}/{} succeeded", attempts, attempts),
+    };
+    draw_text(&format!("{} ([ and ] to switch)", caption), 10.0, 24.0, 24.0, WHITE);
+}
+
+fn draw_pins(assets: &[Texture2D], view: &View, map: &map::Map, pins: &map::Grid, impossible: &[map::Position]) {
+    let size = view.tile_size;
+
+    for (index, cell) in pins.tiles.iter().enumerate() {
+        let (x, y) = view.get_cell_xy((index % pins.size, index / pins.size), pins.size);
+        if let Some(cell) = cell {
+            draw_tile(assets, view, &map.variants[cell.variant], cell.path, x, y);
+            draw_rectangle_lines(x, y, size, size, 3.0, YELLOW);
+        }
+    }
+
+    for position in impossible.iter() {
+        let (x, y) = view.get_cell_xy(*position, pins.size);
+        draw_rectangle(x, y, size, size, Color::new(1.0, 0.0, 0.0, 0.5));
+    }
 }
 
-fn draw_tile(assets: &[Texture2D], tile: &map::Tile, x: f32, y: f32, rotation: f32) {
+fn draw_tile(assets: &[Texture2D], view: &View, tile: &map::Tile, path: map::Path, x: f32, y: f32) {
     let texture = assets[tile.asset];
-    let h_tile_size = DISPLAY_SIZE / 2.0;
+    let size = view.tile_size;
+    let h_tile_size = size / 2.0;
 
     draw_texture_ex(
         texture,
@@ -142,30 +504,30 @@ fn draw_tile(assets: &[Texture2D], tile: &map::Tile, x: f32, y: f32, rotation: f
         y,
         DARKGRAY,
         DrawTextureParams {
-            rotation,
-            dest_size: Some(vec2(DISPLAY_SIZE, DISPLAY_SIZE)),
+            rotation: get_rotation(tile),
+            dest_size: Some(vec2(size, size)),
             ..Default::default()
         },
     );
 
-    match tile.path {
-        map::Path::Entrance => draw_circle(x + h_tile_size, y + h_tile_size, 4.0, GREEN),
-        map::Path::Track => draw_circle(x + h_tile_size, y + h_tile_size, 2.0, BLUE),
-        map::Path::Exit => draw_circle(x + h_tile_size, y + h_tile_size, 4.0, RED),
+    match path {
+        map::Path::Entrance => draw_circle(x + h_tile_size, y + h_tile_size, size / 16.0, GREEN),
+        map::Path::Track => draw_circle(x + h_tile_size, y + h_tile_size, size / 32.0, BLUE),
+        map::Path::Exit => draw_circle(x + h_tile_size, y + h_tile_size, size / 16.0, RED),
         _ => {}
     }
 
     let x = x - 1.0;
     let y = y - 1.0;
-    let n = DISPLAY_SIZE / (TILE_SIZE - 1.0);
+    let n = size / (TILE_SIZE - 1.0);
     for (i, _) in tile.edges.north.iter().enumerate().filter(|e| e.1 > &0).map(|(i, e)| (i as f32, e)) {
         draw_rectangle(x + i * n, y, 2.0, 2.0, ORANGE);
     }
     for (i, _) in tile.edges.east.iter().enumerate().filter(|e| e.1 > &0).map(|(i, e)| (i as f32, e)) {
-        draw_rectangle(x + DISPLAY_SIZE, y + i * n, 2.0, 2.0, ORANGE);
+        draw_rectangle(x + size, y + i * n, 2.0, 2.0, ORANGE);
     }
     for (i, _) in tile.edges.south.iter().enumerate().filter(|e| e.1 > &0).map(|(i, e)| (i as f32, e)) {
-        draw_rectangle(x + i * n, y + DISPLAY_SIZE, 2.0, 2.0, ORANGE);
+        draw_rectangle(x + i * n, y + size, 2.0, 2.0, ORANGE);
     }
     for (i, _) in tile.edges.west.iter().enumerate().filter(|e| e.1 > &0).map(|(i, e)| (i as f32, e)) {
         draw_rectangle(x, y + i * n, 2.0, 2.0, ORANGE);
@@ -0,0 +1,133 @@
+use super::grid::Direction;
+use super::{Config, Map};
+use std::fmt;
+
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Variants that have no compatible neighbor on the given side.
+    pub isolated_sides: Vec<(usize, Direction)>,
+    /// Variants that can never be placed along the border.
+    pub no_border: Vec<usize>,
+    /// Sockets on a side that never appear on the opposing side of any variant.
+    pub unmatched_sockets: Vec<(Direction, Vec<u8>)>,
+    /// Groups of variants that can be placed next to each other, a valid tileset has exactly one.
+    pub classes: Vec<Vec<usize>>,
+    pub has_entrance: bool,
+    pub has_exit: bool,
+}
+
+impl Analysis {
+    pub fn is_valid(&self) -> bool {
+        self.isolated_sides.is_empty() && self.unmatched_sockets.is_empty() && self.classes.len() <= 1
+    }
+}
+
+impl Map {
+    /// Analyzes the tileset of `config` as it would be loaded into this map, leaving the map itself untouched.
+    pub fn analyze(&self, config: &Config) -> Analysis {
+        let mut map = Map::new(self.size, self.path_length.clone());
+        map.border = self.border.clone();
+        map.load_config(config);
+        map.analyze_loaded(config)
+    }
+
+    fn analyze_loaded(&self, config: &Config) -> Analysis {
+        let mut analysis = Analysis::default();
+
+        for (index, variant) in self.variants.iter().enumerate() {
+            for direction in Direction::ALL.iter() {
+                if self.compatible_variants(index, direction).is_empty() {
+                    analysis.isolated_sides.push((index, direction.clone()));
+                }
+            }
+
+            if Direction::ALL.iter().all(|direction| !self.fits_border(variant, direction)) {
+                analysis.no_border.push(index);
+            }
+        }
+
+        for direction in Direction::ALL.iter() {
+            for variant in self.variants.iter() {
+                let socket = variant.edges.get(direction);
+                if analysis.unmatched_sockets.iter().any(|(d, s)| d == direction && s == socket) {
+                    continue;
+                }
+
+                if !self.variants.iter().any(|other| self.fits(variant, other, direction)) {
+                    analysis.unmatched_sockets.push((direction.clone(), socket.clone()));
+                }
+            }
+        }
+
+        let mut visited = vec![false; self.variants.len()];
+        for start in 0..self.variants.len() {
+            if visited[start] {
+                continue;
+            }
+
+            let mut class = vec![];
+            let mut stack = vec![start];
+            visited[start] = true;
+
+            while let Some(index) = stack.pop() {
+                class.push(index);
+                for direction in Direction::ALL.iter() {
+                    for neighbor in self.compatible_variants(index, direction) {
+                        if !visited[neighbor] {
+                            visited[neighbor] = true;
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+            }
+
+            class.sort();
+            analysis.classes.push(class);
+        }
+
+        let has_asset = |index: usize| self.variants.iter().any(|v| v.asset == index);
+        analysis.has_entrance = config.variants.iter().any(|v| v.entrance && has_asset(v.index));
+        analysis.has_exit = config.variants.iter().any(|v| v.exit && has_asset(v.index));
+
+        analysis
+    }
+
+    /// Variants that may be placed on the given side of the variant at `index`.
+    pub fn compatible_variants(&self, index: usize, direction: &Direction) -> Vec<usize> {
+        let variant = &self.variants[index];
+        self.variants
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| self.fits(variant, other, direction))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, direction) in self.isolated_sides.iter() {
+            writeln!(f, "Variant {} has no compatible neighbor to the {:?}", index, direction)?;
+        }
+        for index in self.no_border.iter() {
+            writeln!(f, "Variant {} can never touch the border", index)?;
+        }
+        for (direction, socket) in self.unmatched_sockets.iter() {
+            writeln!(f, "Socket {:?} on the {:?} side has no partner", socket, direction)?;
+        }
+        if self.classes.len() > 1 {
+            writeln!(f, "Variants are split into {} disconnected classes:", self.classes.len())?;
+            for class in self.classes.iter() {
+                writeln!(f, "  {:?}", class)?;
+            }
+        }
+        if !self.has_entrance {
+            writeln!(f, "No entrance variant, entrance will be placed at random")?;
+        }
+        if !self.has_exit {
+            writeln!(f, "No exit variant, exit will be placed at random")?;
+        }
+
+        write!(f, "Tileset is {}", if self.is_valid() { "valid" } else { "invalid" })
+    }
+}
@@ -0,0 +1,89 @@
+use super::grid::Direction;
+use super::Map;
+
+/// Set of variant indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub fn full(len: usize) -> Self {
+        let mut set = Self::new(len);
+        for index in 0..len {
+            set.insert(index);
+        }
+
+        set
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn intersect_with(&mut self, other: &Bitset) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= other;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(i, word)| (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| i * 64 + bit))
+    }
+}
+
+/// Which variants may be placed next to each other, computed once per config.
+#[derive(Debug, Clone, Default)]
+pub struct Compatibility {
+    /// For every variant and direction, the variants that may have it on their `direction` side.
+    pub neighbors: Vec<[Bitset; 4]>,
+    /// For every direction, the variants that may face the border with their `direction` side.
+    pub border: [Bitset; 4],
+}
+
+impl Compatibility {
+    pub fn new(map: &Map) -> Self {
+        let len = map.variants.len();
+
+        let neighbors = map
+            .variants
+            .iter()
+            .map(|neighbor| {
+                Direction::ALL.map(|direction| {
+                    let mut set = Bitset::new(len);
+                    for (index, variant) in map.variants.iter().enumerate() {
+                        if map.fits(variant, neighbor, &direction) {
+                            set.insert(index);
+                        }
+                    }
+                    set
+                })
+            })
+            .collect();
+
+        let border = Direction::ALL.map(|direction| {
+            let mut set = Bitset::new(len);
+            for (index, variant) in map.variants.iter().enumerate() {
+                if map.fits_border(variant, &direction) {
+                    set.insert(index);
+                }
+            }
+            set
+        });
+
+        Self { neighbors, border }
+    }
+}
@@ -0,0 +1,73 @@
+use super::{Config, Map, Path, Position};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::ops::Range;
+
+pub struct Floor {
+    pub size: usize,
+    pub path_length: Range<usize>,
+    pub config: Config,
+}
+
+/// A stack of maps where the exit of each floor is the entrance of the next.
+#[derive(Debug)]
+pub struct Dungeon {
+    pub seed: u64,
+    pub floors: Vec<Map>,
+}
+
+/// Why a dungeon could not be built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DungeonError {
+    /// The floor at the index is smaller than the one above it, so the stairs could end up outside of it.
+    Size(usize),
+    /// No entrance variant of the floor at the index fits where the stairs arrive.
+    Entrance(usize, Position),
+}
+
+impl fmt::Display for DungeonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DungeonError::Size(floor) => write!(f, "floor {} is smaller than the floor above", floor),
+            DungeonError::Entrance(floor, (x, y)) => write!(f, "no entrance fits at ({}, {}) on floor {}", x, y, floor),
+        }
+    }
+}
+
+impl Dungeon {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, floors: vec![] }
+    }
+
+    pub fn build(&mut self, floors: &[Floor], log_history: bool) -> Result<(), DungeonError> {
+        self.floors.clear();
+
+        if let Some(index) = (1..floors.len()).find(|i| floors[*i].size < floors[i - 1].size) {
+            return Err(DungeonError::Size(index));
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut entrance = None;
+
+        for (index, floor) in floors.iter().enumerate() {
+            let mut floor_rng = StdRng::seed_from_u64(rng.gen());
+            let mut map = Map::new(floor.size, floor.path_length.clone());
+
+            if let Some(position) = entrance {
+                map.load_config(&floor.config);
+                if !map.fits_entrance(&position) {
+                    return Err(DungeonError::Entrance(index, position));
+                }
+            }
+
+            map.entrance = entrance;
+            map.build(&mut floor_rng, &floor.config, log_history);
+
+            entrance = map.history.last().unwrap().get_by_path(&Path::Exit).first().copied();
+            self.floors.push(map);
+        }
+
+        Ok(())
+    }
+}
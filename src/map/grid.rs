@@ -1,8 +1,10 @@
-use super::Tile;
+use super::{pathfinding, Cell, Path, Tile};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 pub type Position = (usize, usize);
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Direction {
     North = 0,
     East = 1,
@@ -10,10 +12,69 @@ pub enum Direction {
     West = 3,
 }
 
-#[derive(Debug, Default, Clone)]
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, (x, y): &Position) -> bool {
+        (self.x..self.x + self.width).contains(x) && (self.y..self.y + self.height).contains(y)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+
+    pub fn grow(&self, amount: usize) -> Rect {
+        Rect {
+            x: self.x.saturating_sub(amount),
+            y: self.y.saturating_sub(amount),
+            width: self.width + amount * 2,
+            height: self.height + amount * 2,
+        }
+    }
+
+    pub fn center(&self) -> Position {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        (self.y..self.y + self.height).flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// What lies beyond the edges of a grid during generation.
+#[derive(Debug, Clone, Default)]
+pub enum Border {
+    /// Sockets facing the border have to be closed.
+    #[default]
+    Closed,
+    /// Variants lining each side of the grid, indexed by `Direction`. Any socket is allowed on sides without variants.
+    Neighbors([Option<Vec<usize>>; 4]),
+}
+
+/// Cells hold indices into a tileset, methods that need the tiles themselves take the tileset as an argument.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Grid {
     pub size: usize,
-    pub tiles: Vec<Option<Tile>>,
+    pub tiles: Vec<Option<Cell>>,
 }
 
 impl Grid {
@@ -24,16 +85,21 @@ impl Grid {
         }
     }
 
-    pub fn get(&self, (x, y): &Position) -> &Option<Tile> {
+    pub fn get(&self, (x, y): &Position) -> &Option<Cell> {
         &self.tiles[y * self.size + x]
     }
 
-    pub fn get_mut(&mut self, (x, y): &Position) -> &mut Option<Tile> {
+    pub fn get_mut(&mut self, (x, y): &Position) -> &mut Option<Cell> {
         &mut self.tiles[y * self.size + x]
     }
 
-    pub fn set(&mut self, (x, y): &Position, tile: Option<Tile>) {
-        self.tiles[y * self.size + x] = tile;
+    pub fn set(&mut self, (x, y): &Position, cell: Option<Cell>) {
+        self.tiles[y * self.size + x] = cell;
+    }
+
+    /// Tile of the cell, `None` if it is empty or refers to a variant that is not part of `tileset`.
+    pub fn tile<'a>(&self, position: &Position, tileset: &'a [Tile]) -> Option<&'a Tile> {
+        self.get(position).and_then(|cell| tileset.get(cell.variant))
     }
 
     pub fn move_position(&self, (x, y): &Position, direction: Direction) -> Option<Position> {
@@ -63,11 +129,11 @@ impl Grid {
         None
     }
 
-    pub fn get_by_asset(&self, asset: usize) -> Vec<Position> {
+    pub fn get_by_asset(&self, asset: usize, tileset: &[Tile]) -> Vec<Position> {
         let mut matches = vec![];
         for y in 0..self.size {
             for x in 0..self.size {
-                if let Some(tile) = self.get(&(x, y)) {
+                if let Some(tile) = self.tile(&(x, y), tileset) {
                     if tile.asset == asset {
                         matches.push((x, y));
                     }
@@ -77,4 +143,156 @@ impl Grid {
 
         matches
     }
+
+    pub fn get_by_path(&self, path: &Path) -> Vec<Position> {
+        (0..self.tiles.len())
+            .map(|index| (index % self.size, index / self.size))
+            .filter(|position| self.get(position).is_some_and(|cell| cell.path == *path))
+            .collect()
+    }
+
+    pub fn count_assets(&self, tileset: &[Tile]) -> HashMap<usize, usize> {
+        let mut counts = HashMap::new();
+        for cell in self.tiles.iter().flatten() {
+            *counts.entry(tileset[cell.variant].asset).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Share of placed tiles that are passable.
+    pub fn open_ratio(&self, tileset: &[Tile]) -> f32 {
+        let placed = self.tiles.iter().flatten().count();
+        if placed == 0 {
+            return 0.0;
+        }
+
+        self.tiles.iter().flatten().filter(|cell| tileset[cell.variant].is_open()).count() as f32 / placed as f32
+    }
+
+    /// Cost of the cheapest walk from every cell to any of `sources`, paying for each tile walked onto, indexed like `tiles`.
+    /// `None` where there is none.
+    pub fn distances(&self, tileset: &[Tile], sources: &[Position]) -> Vec<Option<usize>> {
+        pathfinding::distances(self, tileset, sources)
+    }
+
+    /// Like `distances`, towards the cells marked with `path`. The cost of walking there, not from there: the tile at the
+    /// entrance or exit is paid for while the cell's own tile is not.
+    pub fn distances_to(&self, tileset: &[Tile], path: &Path) -> Vec<Option<usize>> {
+        self.distances(tileset, &self.get_by_path(path))
+    }
+
+    /// For every cell, the direction of the first step of its cheapest walk to any of `sources`. `None` at the sources
+    /// themselves and where they cannot be reached.
+    ///
+    /// Following the flow from cell to cell is only valid without intra-tile connections: a walk that entered a tile
+    /// through one of its corridors may not be able to leave it the way a walk starting there would.
+    pub fn flow(&self, tileset: &[Tile], sources: &[Position]) -> Vec<Option<Direction>> {
+        pathfinding::flow(self, tileset, sources)
+    }
+
+    /// Checks a grid that was not generated with `tileset` against it, e.g. after loading it from disk. Neighbors have to
+    /// match edges, `Map::validate` checks them against the adjacency rules of the map instead.
+    pub fn validate(&self, tileset: &[Tile]) -> Vec<Violation> {
+        self.validate_with(
+            tileset,
+            |tile, neighbor, direction| tile.edges.get(direction) == neighbor.edges.get(&direction.opposite()),
+            |tile, direction| !tile.edges.is_open(direction),
+        )
+    }
+
+    /// Like `validate`, with `fits` deciding whether a tile may have a neighbor on a side and `fits_border` whether it may
+    /// face the border.
+    pub fn validate_with(
+        &self,
+        tileset: &[Tile],
+        fits: impl Fn(&Tile, &Tile, &Direction) -> bool,
+        fits_border: impl Fn(&Tile, &Direction) -> bool,
+    ) -> Vec<Violation> {
+        let mut violations = vec![];
+        let positions = || (0..self.tiles.len()).map(|index| (index % self.size, index / self.size));
+        let tile = |position: &Position| self.tile(position, tileset);
+
+        for position in positions() {
+            if self.get(&position).is_some() && tile(&position).is_none() {
+                violations.push(Violation::UnknownVariant(position));
+                continue;
+            }
+            let Some(current) = tile(&position) else {
+                continue;
+            };
+
+            for direction in Direction::ALL {
+                let Some(next) = self.move_position(&position, direction.clone()) else {
+                    if !fits_border(current, &direction) {
+                        violations.push(Violation::OpenBorder(position, direction));
+                    }
+                    continue;
+                };
+
+                // Every pair is compared once, from its northern or western cell.
+                let neighbor = tile(&next).filter(|_| matches!(direction, Direction::East | Direction::South));
+                if neighbor.is_some_and(|neighbor| !fits(current, neighbor, &direction)) {
+                    violations.push(Violation::EdgeMismatch(position, direction));
+                }
+            }
+        }
+
+        let entrances = self.get_by_path(&Path::Entrance);
+        match entrances.len() {
+            0 => violations.push(Violation::MissingEntrance),
+            1 => {}
+            _ => violations.push(Violation::DuplicateEntrance(entrances.clone())),
+        }
+        let exits = self.get_by_path(&Path::Exit);
+        match exits.len() {
+            0 => violations.push(Violation::MissingExit),
+            1 => {}
+            _ => violations.push(Violation::DuplicateExit(exits)),
+        }
+
+        // Walk the path from the entrance the way the generator does, every path cell should be reached.
+        let is_path = |position: &Position| self.get(position).is_some_and(|cell| cell.path != Path::None);
+        let reached: HashSet<Position> = entrances
+            .into_iter()
+            .flat_map(|entrance| pathfinding::reachable(self, tileset, entrance, is_path))
+            .collect();
+        for position in positions().filter(|p| is_path(p) && !reached.contains(p)) {
+            violations.push(Violation::DisconnectedPath(position));
+        }
+
+        violations
+    }
+}
+
+/// Something wrong with a grid, found by `Grid::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The edges of the cell and its neighbor in the direction differ.
+    EdgeMismatch(Position, Direction),
+    /// The cell is open towards the border of the grid.
+    OpenBorder(Position, Direction),
+    /// The cell refers to a variant that is not part of the tileset.
+    UnknownVariant(Position),
+    MissingEntrance,
+    DuplicateEntrance(Vec<Position>),
+    MissingExit,
+    DuplicateExit(Vec<Position>),
+    /// The path cell cannot be reached from the entrance along the path.
+    DisconnectedPath(Position),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::EdgeMismatch((x, y), direction) => write!(f, "edges do not match {:?} of ({}, {})", direction, x, y),
+            Violation::OpenBorder((x, y), direction) => write!(f, "({}, {}) is open towards the {:?} border", x, y, direction),
+            Violation::UnknownVariant((x, y)) => write!(f, "unknown variant at ({}, {})", x, y),
+            Violation::MissingEntrance => write!(f, "no entrance"),
+            Violation::DuplicateEntrance(positions) => write!(f, "{} entrances", positions.len()),
+            Violation::MissingExit => write!(f, "no exit"),
+            Violation::DuplicateExit(positions) => write!(f, "{} exits", positions.len()),
+            Violation::DisconnectedPath((x, y)) => write!(f, "path is broken at ({}, {})", x, y),
+        }
+    }
 }
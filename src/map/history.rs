@@ -0,0 +1,203 @@
+use super::grid::{Grid, Position};
+use super::{Cell, Constraint};
+use std::fmt;
+
+/// What happened in a step of the generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// Overview of all variants, shown before the generation starts.
+    Tileset,
+    /// The entrance tile was placed to grow the map from.
+    Seed,
+    /// A cell was collapsed to one of its candidates.
+    Collapse,
+    /// A cell was left without candidates and the attempt failed.
+    Contradiction,
+    /// A placed tile was replaced to reach the minimum count of an asset.
+    Backtrack,
+    /// The path from the entrance to the exit was marked.
+    Path,
+    /// Tiles that cannot be reached from the exit were removed.
+    Prune,
+}
+
+/// A cell that changed in a step.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub position: Position,
+    pub old: Option<Cell>,
+    pub new: Option<Cell>,
+}
+
+/// What happened in a step and the cells it changed.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub kind: StepKind,
+    /// Cell the step is about.
+    pub position: Option<Position>,
+    /// Number of variants the cell could have been collapsed to.
+    pub candidates: usize,
+    /// Shannon entropy of the candidate weights.
+    pub entropy: f32,
+    /// Empty for keyframes, which store the whole grid instead.
+    pub changes: Vec<Change>,
+}
+
+impl Step {
+    pub fn new(kind: StepKind) -> Self {
+        Self {
+            kind,
+            position: None,
+            candidates: 0,
+            entropy: 0.0,
+            changes: vec![],
+        }
+    }
+}
+
+/// Grids of the generation, stored as keyframes with the changes in between.
+#[derive(Debug, Clone)]
+pub struct History {
+    /// Constraints the grids were generated under, unconstrained when empty.
+    pub constraints: Vec<Option<Constraint>>,
+    steps: Vec<Step>,
+    /// Full grids by step index, taken whenever the grid size changes and periodically so lookups stay fast.
+    keyframes: Vec<(usize, Grid)>,
+    current: Grid,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            constraints: vec![],
+            steps: vec![],
+            keyframes: vec![],
+            current: Grid::new(0),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Grid after the last step.
+    pub fn last(&self) -> Option<&Grid> {
+        (!self.is_empty()).then_some(&self.current)
+    }
+
+    /// Records the step, storing only the cells that differ from the previous grid.
+    pub fn push(&mut self, mut step: Step, grid: &Grid) {
+        let index = self.steps.len();
+        let since_keyframe = self.keyframes.last().map_or(usize::MAX, |(i, _)| index - i);
+
+        if grid.size != self.current.size || since_keyframe >= grid.tiles.len().max(64) {
+            step.changes.clear();
+            self.keyframes.push((index, grid.clone()));
+            self.current = grid.clone();
+        } else {
+            step.changes = self
+                .current
+                .tiles
+                .iter()
+                .zip(grid.tiles.iter())
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(i, (old, new))| Change {
+                    position: (i % grid.size, i / grid.size),
+                    old: *old,
+                    new: *new,
+                })
+                .collect();
+
+            for change in step.changes.iter() {
+                self.current.set(&change.position, change.new);
+            }
+        }
+
+        self.steps.push(step);
+    }
+
+    /// Reconstructs the grid after the step at `index` from the closest keyframe.
+    pub fn get(&self, index: usize) -> Grid {
+        assert!(index < self.steps.len(), "Step {} is not part of the history!", index);
+
+        let keyframe = self.keyframes.partition_point(|(i, _)| *i <= index) - 1;
+        let (start, grid) = &self.keyframes[keyframe];
+        let mut grid = grid.clone();
+
+        for step in self.steps[start + 1..=index].iter() {
+            for change in step.changes.iter() {
+                grid.set(&change.position, change.new);
+            }
+        }
+
+        grid
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shannon entropy of a set of weights.
+pub fn entropy(weights: &[f32]) -> f32 {
+    let sum: f32 = weights.iter().sum();
+    if sum <= 0.0 {
+        return 0.0;
+    }
+
+    let log_sum: f32 = weights.iter().filter(|w| **w > 0.0).map(|w| w * w.ln()).sum();
+    sum.ln() - log_sum / sum
+}
+
+/// Why an attempt was abandoned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// No entrance variant fits at the position.
+    Entrance(Position),
+    /// The cell was left without candidates.
+    Contradiction(Position),
+    /// An asset could not be placed as often as its minimum requires.
+    Minimum,
+    /// No tile could be picked as the exit.
+    Exit,
+    /// The exit cannot be reached from the entrance.
+    NoPath,
+    /// The path has the given cost, which is outside of `Map::path_length`.
+    PathLength(usize),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Entrance((x, y)) => write!(f, "no entrance fits at ({}, {})", x, y),
+            Failure::Contradiction((x, y)) => write!(f, "contradiction at ({}, {})", x, y),
+            Failure::Minimum => write!(f, "asset minimums not met"),
+            Failure::Exit => write!(f, "no exit tile"),
+            Failure::NoPath => write!(f, "exit cannot be reached"),
+            Failure::PathLength(length) => write!(f, "path length {} out of range", length),
+        }
+    }
+}
+
+/// History of a single try at generating the map.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    /// Steps of the attempt, only kept when the history is logged.
+    pub history: Option<History>,
+    pub failure: Failure,
+}
@@ -0,0 +1,250 @@
+use super::grid::{Direction, Position, Rect};
+use super::{Tile, VariantKey};
+use rand::Rng;
+use std::collections::VecDeque;
+
+/// Restricts which variants may be placed in a cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// At least one side has to be open.
+    Open,
+    /// Every side has to be closed.
+    Wall,
+    /// Sides that have to be open (`Some(true)`) or closed (`Some(false)`), indexed by `Direction`.
+    Sockets([Option<bool>; 4]),
+    /// Only the given rotation of an asset.
+    Variant(VariantKey),
+}
+
+impl Constraint {
+    pub fn allows(&self, tile: &Tile) -> bool {
+        match self {
+            Constraint::Open => tile.is_open(),
+            Constraint::Wall => !tile.is_open(),
+            Constraint::Sockets(sockets) => Direction::ALL
+                .iter()
+                .zip(sockets)
+                .all(|(direction, socket)| socket.is_none_or(|open| tile.edges.is_open(direction) == open)),
+            Constraint::Variant(key) => tile.key() == *key,
+        }
+    }
+}
+
+/// Coarse rooms and corridors layout, rasterized into constraints that guide the tile generation.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub size: usize,
+    pub rooms: Vec<Rect>,
+    /// Pairs of rooms connected by a corridor.
+    pub corridors: Vec<(usize, usize)>,
+    /// Rooms passed on the way from the entrance to the exit.
+    pub critical_path: Vec<usize>,
+    pub entrance: Position,
+    pub exit: Position,
+    /// Constrain cells outside of rooms and corridors to be walls.
+    pub walls: bool,
+}
+
+impl Layout {
+    pub fn generate(rng: &mut impl Rng, size: usize, room_count: usize) -> Self {
+        assert!(size >= 5, "Map is too small for a layout!");
+        assert!(room_count > 0, "Layout needs at least one room!");
+
+        let max_room = (size / 3).max(2);
+        let mut rooms: Vec<Rect> = vec![];
+
+        for _ in 0..room_count * 20 {
+            if rooms.len() == room_count {
+                break;
+            }
+
+            let width = rng.gen_range(1..=max_room);
+            let height = rng.gen_range(1..=max_room);
+            let room = Rect {
+                x: rng.gen_range(1..size - width),
+                y: rng.gen_range(1..size - height),
+                width,
+                height,
+            };
+
+            if !rooms.iter().any(|other| other.grow(1).intersects(&room)) {
+                rooms.push(room);
+            }
+        }
+
+        // Connect the rooms with a minimum spanning tree over the distance between their centers.
+        let mut corridors = vec![];
+        let mut connected = vec![0];
+        while connected.len() < rooms.len() {
+            let (from, to) = connected
+                .iter()
+                .flat_map(|a| (0..rooms.len()).filter(|b| !connected.contains(b)).map(move |b| (*a, b)))
+                .min_by_key(|(a, b)| distance(rooms[*a].center(), rooms[*b].center()))
+                .unwrap();
+
+            corridors.push((from, to));
+            connected.push(to);
+        }
+
+        let critical_path = longest_path(rooms.len(), &corridors);
+
+        let mut layout = Self {
+            size,
+            rooms,
+            corridors,
+            critical_path,
+            entrance: (0, 0),
+            exit: (0, 0),
+            walls: false,
+        };
+
+        let occupied = layout.open_sides();
+        layout.entrance = layout.pick_stairs(rng, layout.critical_path[0], &occupied, None);
+        layout.exit = layout.pick_stairs(rng, *layout.critical_path.last().unwrap(), &occupied, Some(layout.entrance));
+        layout
+    }
+
+    /// Per cell constraints, indexed like `Grid::tiles`.
+    pub fn constraints(&self) -> Vec<Option<Constraint>> {
+        let mut constraints: Vec<Option<Constraint>> = self
+            .open_sides()
+            .into_iter()
+            .map(|sides| sides.map(|sides| Constraint::Sockets(sides.map(|open| if open { Some(true) } else { None }))))
+            .collect();
+
+        if self.walls {
+            for constraint in constraints.iter_mut().filter(|c| c.is_none()) {
+                *constraint = Some(Constraint::Wall);
+            }
+        }
+
+        for stairs in [self.entrance, self.exit] {
+            let room = Direction::ALL
+                .iter()
+                .find(|direction| move_position(stairs, direction, self.size).is_some_and(|p| self.rooms.iter().any(|r| r.contains(&p))));
+
+            constraints[stairs.1 * self.size + stairs.0] = Some(Constraint::Sockets(
+                Direction::ALL.map(|direction| Some(Some(&direction) == room)),
+            ));
+        }
+
+        constraints
+    }
+
+    /// Sides that have to be open for every cell covered by a room or corridor.
+    fn open_sides(&self) -> Vec<Option<[bool; 4]>> {
+        let mut sides: Vec<Option<[bool; 4]>> = vec![None; self.size * self.size];
+        let mut connect = |from: Position, to: Position| {
+            for direction in Direction::ALL {
+                if move_position(from, &direction, self.size) == Some(to) {
+                    sides[from.1 * self.size + from.0].get_or_insert([false; 4])[direction.clone() as usize] = true;
+                    sides[to.1 * self.size + to.0].get_or_insert([false; 4])[direction.opposite() as usize] = true;
+                }
+            }
+        };
+
+        for room in self.rooms.iter() {
+            for position in room.positions() {
+                for direction in [Direction::East, Direction::South] {
+                    if let Some(next) = move_position(position, &direction, self.size).filter(|p| room.contains(p)) {
+                        connect(position, next);
+                    }
+                }
+            }
+        }
+
+        for (a, b) in self.corridors.iter() {
+            let ((ax, ay), (bx, by)) = (self.rooms[*a].center(), self.rooms[*b].center());
+            let mut path = vec![(ax, ay)];
+
+            let mut x = ax;
+            while x != bx {
+                x = if bx > x { x + 1 } else { x - 1 };
+                path.push((x, ay));
+            }
+
+            let mut y = ay;
+            while y != by {
+                y = if by > y { y + 1 } else { y - 1 };
+                path.push((bx, y));
+            }
+
+            for cells in path.windows(2) {
+                connect(cells[0], cells[1]);
+            }
+        }
+
+        for room in self.rooms.iter() {
+            for position in room.positions() {
+                sides[position.1 * self.size + position.0].get_or_insert([false; 4]);
+            }
+        }
+
+        sides
+    }
+
+    /// Picks a free cell next to the room to place stairs on.
+    fn pick_stairs(&self, rng: &mut impl Rng, room: usize, occupied: &[Option<[bool; 4]>], taken: Option<Position>) -> Position {
+        let room = &self.rooms[room];
+        let candidates: Vec<Position> = room
+            .positions()
+            .flat_map(|position| Direction::ALL.iter().filter_map(move |d| move_position(position, d, self.size)))
+            .filter(|p| occupied[p.1 * self.size + p.0].is_none() && Some(*p) != taken)
+            .collect();
+
+        if candidates.is_empty() {
+            room.center()
+        } else {
+            candidates[rng.gen_range(0..candidates.len())]
+        }
+    }
+}
+
+fn move_position((x, y): Position, direction: &Direction, size: usize) -> Option<Position> {
+    match direction {
+        Direction::North => y.checked_sub(1).map(|y| (x, y)),
+        Direction::East => Some((x + 1, y)).filter(|(x, _)| *x < size),
+        Direction::South => Some((x, y + 1)).filter(|(_, y)| *y < size),
+        Direction::West => x.checked_sub(1).map(|x| (x, y)),
+    }
+}
+
+fn distance((ax, ay): Position, (bx, by): Position) -> usize {
+    ax.abs_diff(bx) + ay.abs_diff(by)
+}
+
+/// Path from the first room to the room furthest away from it in the corridor tree.
+fn longest_path(rooms: usize, corridors: &[(usize, usize)]) -> Vec<usize> {
+    let mut previous = vec![None; rooms];
+    let mut visited = vec![false; rooms];
+    let mut queue = VecDeque::from([0]);
+    let mut last = 0;
+    visited[0] = true;
+
+    while let Some(room) = queue.pop_front() {
+        last = room;
+        for (a, b) in corridors.iter() {
+            let next = if *a == room {
+                *b
+            } else if *b == room {
+                *a
+            } else {
+                continue;
+            };
+
+            if !visited[next] {
+                visited[next] = true;
+                previous[next] = Some(room);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut path = vec![last];
+    while let Some(room) = previous[*path.last().unwrap()] {
+        path.push(room);
+    }
+
+    path.reverse();
+    path
+}
@@ -1,31 +1,86 @@
+use compatibility::{Bitset, Compatibility};
 use image::DynamicImage;
 use itertools::Itertools;
 use rand::distributions::WeightedIndex;
 use rand::prelude::Distribution;
-use rand::{rngs::ThreadRng, Rng};
-use std::ops::Range;
+use rand::Rng;
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
 use std::time::Instant;
+mod analysis;
+mod compatibility;
+mod dungeon;
 mod grid;
+mod history;
+mod layout;
+mod overlapping;
 mod pathfinding;
+mod rules;
 mod tile;
-use self::grid::{Direction, Grid, Position};
+mod weight;
+mod world;
+pub use self::grid::{Border, Direction, Grid, Position, Rect, Violation};
+pub use analysis::Analysis;
+pub use dungeon::{Dungeon, DungeonError, Floor};
+pub use history::{Attempt, Change, Failure, History, Step, StepKind};
+pub use layout::{Constraint, Layout};
+pub use overlapping::Overlapping;
+pub use rules::{Example, Rules, VariantKey};
 pub use tile::Edges;
 pub use tile::Path;
-pub use tile::Tile;
+pub use tile::{Cell, Connections, Tile};
+pub use weight::{WeightContext, WeightRule};
+pub use world::{ChunkCoord, World};
 
 #[derive(Debug)]
 pub struct Map {
     pub size: usize,
+    /// Allowed cost of the path from the entrance to the exit, summed over the tiles walked onto.
     pub path_length: Range<usize>,
-    pub history: Vec<Grid>,
+    /// Pins the entrance to a position instead of picking one at random.
+    pub entrance: Option<Position>,
+    /// Pins the exit to a position instead of picking one at random.
+    pub exit: Option<Position>,
+    /// Per cell constraints indexed like `Grid::tiles`, unconstrained when empty.
+    pub constraints: Vec<Option<Constraint>>,
+    /// Steps of the successful attempt.
+    pub history: History,
+    /// Attempts that were abandoned before the successful one, in order.
+    pub failed_attempts: Vec<Attempt>,
     pub variants: Vec<Tile>,
+    /// Assets the entrance and exit may be placed on, anywhere when empty.
+    pub entrance_assets: Vec<usize>,
+    pub exit_assets: Vec<usize>,
+    pub rules: Option<Rules>,
+    pub weight_rules: HashMap<usize, Vec<WeightRule>>,
+    /// How many times each asset may be placed, keyed by asset index.
+    pub limits: HashMap<usize, RangeInclusive<usize>>,
+    /// Target share of passable tiles, weights are adjusted during generation to approach it.
+    pub open_ratio: Option<f32>,
+    /// Share of passable tiles in the built map, measured after pruning.
+    pub achieved_open_ratio: Option<f32>,
+    /// What lies beyond the edges of the map.
+    pub border: Border,
+    /// Attempts at regenerating a region before giving up on it.
+    pub max_tries: usize,
+    compatibility: Compatibility,
 }
 
 pub struct Variants {
     pub index: usize,
     pub weight: f32,
+    /// Adjust `weight` depending on where the variant is about to be placed.
+    pub weight_rules: Vec<WeightRule>,
     pub entrance: bool,
     pub exit: bool,
+    /// Least number of times the asset has to be placed.
+    pub min: Option<usize>,
+    /// Most number of times the asset may be placed.
+    pub max: Option<usize>,
+    /// Cost of walking onto the asset, `None` if it cannot be walked on.
+    pub cost: Option<usize>,
+    /// Groups of sides that are joined within the unrotated asset, overriding what is derived from the image.
+    pub connections: Option<Vec<Vec<Direction>>>,
 }
 
 impl Default for Variants {
@@ -33,15 +88,26 @@ impl Default for Variants {
         Self {
             index: Default::default(),
             weight: 1.0,
+            weight_rules: vec![],
             entrance: false,
             exit: false,
+            min: None,
+            max: None,
+            cost: Some(1),
+            connections: None,
         }
     }
 }
 
+#[derive(Default)]
 pub struct Config {
     pub image: Option<(DynamicImage, u32)>,
     pub variants: Vec<Variants>,
+    pub rules: Option<Rules>,
+    /// Target share of passable tiles, between 0 and 1.
+    pub open_ratio: Option<f32>,
+    /// Only join the sides of a tile whose open pixels are connected, instead of all open sides.
+    pub sub_tile_paths: bool,
 }
 
 impl Map {
@@ -49,176 +115,343 @@ impl Map {
         Self {
             size,
             path_length,
-            history: vec![],
+            entrance: None,
+            exit: None,
+            constraints: vec![],
+            history: History::new(),
+            failed_attempts: vec![],
             variants: vec![],
+            entrance_assets: vec![],
+            exit_assets: vec![],
+            rules: None,
+            weight_rules: HashMap::new(),
+            limits: HashMap::new(),
+            open_ratio: None,
+            achieved_open_ratio: None,
+            border: Border::Closed,
+            max_tries: 1000,
+            compatibility: Compatibility::default(),
         }
     }
 
-    fn pick_entrance_exit(&self, rng: &mut ThreadRng, grid: &Grid, variants: Vec<usize>) -> grid::Position {
-        if variants.len() > 0 {
-            let candidates = grid.get_by_asset(variants[rng.gen_range(0..variants.len())]);
-            candidates[rng.gen_range(0..candidates.len())]
+    /// Picks the exit, never at the entrance since the path between them would be empty.
+    fn pick_exit(&self, rng: &mut impl Rng, grid: &Grid, entrance: &Position, region: Option<&Rect>) -> Option<grid::Position> {
+        let assets = &self.exit_assets;
+        if let Some(exit) = self.exit {
+            let tile = grid.tile(&exit, &self.variants)?;
+            return (assets.is_empty() || assets.contains(&tile.asset)).then_some(exit);
+        }
+
+        if !assets.is_empty() {
+            let candidates: Vec<Position> = assets
+                .iter()
+                .flat_map(|asset| grid.get_by_asset(*asset, &self.variants))
+                .filter(|position| position != entrance)
+                .collect();
+            (!candidates.is_empty()).then(|| candidates[rng.gen_range(0..candidates.len())])
         } else {
-            (rng.gen_range(0..grid.size - 1), rng.gen_range(0..grid.size - 1))
+            let (columns, rows) = match region {
+                // The pruned cells around a regenerated region stay empty, so the exit is picked within it.
+                Some(rect) => (rect.x..rect.x + rect.width, rect.y..rect.y + rect.height),
+                None => (0..grid.size - 1, 0..grid.size - 1),
+            };
+            let candidates: Vec<Position> = rows
+                .flat_map(|y| columns.clone().map(move |x| (x, y)))
+                .filter(|position| position != entrance)
+                .collect();
+            (!candidates.is_empty()).then(|| candidates[rng.gen_range(0..candidates.len())])
         }
     }
 
-    pub fn build(&mut self, rng: &mut ThreadRng, config: &Config, log_history: bool) {
+    /// Guides the generation with a rooms and corridors layout, pinning the entrance and exit to its stairs.
+    pub fn apply_layout(&mut self, layout: &Layout) {
+        self.size = layout.size;
+        self.constraints = layout.constraints();
+        self.entrance = Some(layout.entrance);
+        self.exit = Some(layout.exit);
+    }
+
+    pub fn build(&mut self, rng: &mut impl Rng, config: &Config, log_history: bool) {
         self.history.clear();
-        let time = Instant::now();
+        self.failed_attempts.clear();
+        self.achieved_open_ratio = None;
+
+        if let Some((x, y)) = self.entrance {
+            assert!(x < self.size && y < self.size, "Entrance is outside of the map!");
+        }
+        if let Some((x, y)) = self.exit {
+            assert!(x < self.size && y < self.size, "Exit is outside of the map!");
+        }
+        assert!(
+            self.constraints.is_empty() || self.constraints.len() == self.size * self.size,
+            "Constraints do not match the map size!"
+        );
 
         self.load_config(config);
-        self.history.push(Grid {
+        let tileset = Grid {
             size: self.variants.len(),
-            tiles: self.variants.iter().map(|v| Some(v.clone())).collect(),
-        });
-
-        let mut tries = 0;
+            tiles: (0..self.variants.len()).map(|v| Some(Cell::new(v))).collect(),
+        };
 
-        loop {
-            tries += 1;
-            let map_ok = self.generate_map(rng, log_history);
-
-            if map_ok {
-                let mut grid = self.history.last().unwrap().clone();
-
-                let entrance = self.pick_entrance_exit(
-                    rng,
-                    &grid,
-                    config
-                        .variants
-                        .iter()
-                        .filter_map(|v| if v.entrance { Some(v.index) } else { None })
-                        .collect(),
-                );
-
-                let exit = self.pick_entrance_exit(
-                    rng,
-                    &grid,
-                    config
-                        .variants
-                        .iter()
-                        .filter_map(|v| if v.exit { Some(v.index) } else { None })
-                        .collect(),
-                );
-
-                match pathfinding::test(&grid, entrance, exit) {
-                    Some((tiles, length)) => {
-                        if self.path_length.contains(&length) {
-                            for index in tiles {
-                                grid.get_mut(&index).as_mut().unwrap().path = if index == entrance {
-                                    Path::Entrance
-                                } else if index == exit {
-                                    Path::Exit
-                                } else {
-                                    Path::Track
-                                };
-                            }
-
-                            self.history.push(grid.clone());
-
-                            for x in 0..grid.size {
-                                for y in 0..grid.size {
-                                    if pathfinding::test(&grid, (x, y), exit).is_none() {
-                                        grid.set(&(x, y), None);
-                                    }
-                                }
-                            }
-
-                            self.history.push(grid.clone());
-                            break;
-                        }
-                    }
-                    None => {}
+        retry("Map", || {
+            self.history.clear();
+            self.history.push(Step::new(StepKind::Tileset), &tileset);
+
+            match self.try_build(rng, log_history) {
+                Ok(()) => Some(()),
+                Err(failure) => {
+                    let history = std::mem::take(&mut self.history);
+                    self.failed_attempts.push(Attempt {
+                        history: log_history.then_some(history),
+                        failure,
+                    });
+                    None
                 }
             }
+        });
+    }
+
+    /// Makes a single attempt at building the map, leaving the result in `history`. Expects the config to be loaded.
+    pub fn try_build(&mut self, rng: &mut impl Rng, log_history: bool) -> Result<(), Failure> {
+        // The entrance is picked before collapsing and seeded with an entrance asset, instead of being picked among the
+        // finished tiles, so that weight rules can measure the distance from it and the map grows outward from it.
+        let entrance = self
+            .entrance
+            .unwrap_or_else(|| (rng.gen_range(0..self.size), rng.gen_range(0..self.size)));
+        let grid = self.clear(rng, &entrance).ok_or(Failure::Entrance(entrance))?;
+        let grid = self.generate_map(rng, grid, &entrance, None, log_history)?;
+
+        let exit = self.pick_exit(rng, &grid, &entrance, None).ok_or(Failure::Exit)?;
+        let grid = self.connect(grid, entrance, exit)?;
+        self.achieved_open_ratio = Some(grid.open_ratio(&self.variants));
+        Ok(())
+    }
+
+    /// Clears and regenerates the cells within `rect`, keeping the rest of the map as it is. Gives up after `max_tries` attempts
+    /// and returns the last failure.
+    pub fn regenerate_region(&mut self, grid: &Grid, rect: &Rect, rng: &mut impl Rng) -> Result<(Grid, History), Failure> {
+        assert!(rect.width > 0 && rect.height > 0, "Region is empty!");
+        assert!(
+            rect.x + rect.width <= grid.size && rect.y + rect.height <= grid.size,
+            "Region is outside of the map!"
+        );
+        let history = std::mem::take(&mut self.history);
+
+        let result = retry_bounded(self.max_tries, || {
+            self.history.clear();
+            self.try_regenerate(grid, rect, rng)
+        });
+        let regenerated = std::mem::replace(&mut self.history, history);
+        result.map(|grid| (grid, regenerated))
+    }
+
+    fn try_regenerate(&mut self, grid: &Grid, rect: &Rect, rng: &mut impl Rng) -> Result<Grid, Failure> {
+        let mut grid = grid.clone();
+        let mut entrance = grid.get_by_path(&Path::Entrance).first().copied().filter(|p| !rect.contains(p));
+        let exit = grid.get_by_path(&Path::Exit).first().copied().filter(|p| !rect.contains(p));
+
+        for tile in grid.tiles.iter_mut().flatten() {
+            tile.path = Path::None;
+        }
+        let positions: Vec<Position> = rect.positions().collect();
+        for position in positions.iter() {
+            grid.set(position, None);
         }
 
-        let elapsed = time.elapsed().as_secs_f32();
+        if entrance.is_none() {
+            let position = positions[rng.gen_range(0..positions.len())];
+            self.seed(rng, &mut grid, &position, Some(rect))
+                .ok_or(Failure::Entrance(position))?;
+            entrance = Some(position);
+        }
 
-        println!("Map generated after {} tries", tries);
-        println!("Time taken: {}", elapsed);
-        println!("Per try: {}", elapsed / tries as f32);
+        let entrance = entrance.unwrap();
+        let grid = self.generate_map(rng, grid, &entrance, Some(rect), true)?;
+        let exit = match exit {
+            Some(exit) => exit,
+            None => self.pick_exit(rng, &grid, &entrance, Some(rect)).ok_or(Failure::Exit)?,
+        };
+        self.connect(grid, entrance, exit)
     }
 
-    fn neighbors_from_image(&self, image: &DynamicImage, tile_size: u32) -> Vec<(usize, Direction, Edges)> {
-        let mut variants = vec![];
+    /// Marks the path between the entrance and exit and removes tiles that cannot be reached.
+    fn connect(&mut self, mut grid: Grid, entrance: Position, exit: Position) -> Result<Grid, Failure> {
+        let (tiles, length) = pathfinding::test(&grid, &self.variants, entrance, exit).ok_or(Failure::NoPath)?;
+        if !self.path_length.contains(&length) {
+            return Err(Failure::PathLength(length));
+        }
 
-        for x in 0..(image.width() / tile_size) {
-            for y in 0..(image.height() / tile_size) {
-                let index = (y * self.size as u32 + x) as usize;
-                let mut variant_img = image.clone().crop(x * tile_size, y * tile_size, tile_size, tile_size);
-                let mut direction = Direction::North;
+        for index in tiles {
+            grid.get_mut(&index).as_mut().unwrap().path = if index == entrance {
+                Path::Entrance
+            } else if index == exit {
+                Path::Exit
+            } else {
+                Path::Track
+            };
+        }
 
-                for _ in 0..4 {
-                    variants.push((index, direction.clone(), variant_img.clone()));
-                    variant_img = variant_img.rotate90();
-                    direction = match direction {
-                        Direction::North => Direction::East,
-                        Direction::East => Direction::South,
-                        Direction::South => Direction::West,
-                        Direction::West => Direction::North,
-                    };
+        self.history.push(Step::new(StepKind::Path), &grid);
+        self.prune(&mut grid, exit);
+
+        if !self.meets_minimum(&grid.count_assets(&self.variants)) {
+            return Err(Failure::Minimum);
+        }
+
+        self.history.push(Step::new(StepKind::Prune), &grid);
+        Ok(grid)
+    }
+
+    /// Removes every tile the exit cannot be reached from.
+    pub fn prune(&self, grid: &mut Grid, exit: Position) {
+        for x in 0..grid.size {
+            for y in 0..grid.size {
+                if pathfinding::test(grid, &self.variants, (x, y), exit).is_none() {
+                    grid.set(&(x, y), None);
                 }
             }
         }
+    }
+
+    fn neighbors_from_image(
+        image: &DynamicImage,
+        tile_size: u32,
+        sub_tile_paths: bool,
+    ) -> Vec<(usize, Direction, Edges, Option<Connections>)> {
+        let mut variants = tile::get_rotations(image, tile_size);
 
         variants.sort_by(|(_, _, a), (_, _, b)| a.as_bytes().cmp(b.as_bytes()));
         variants.dedup_by(|(a, _, ai), (b, _, bi)| a == b && ai.as_bytes() == bi.as_bytes());
         variants
             .into_iter()
-            .map(|(index, direction, image)| (index, direction, tile::get_edges(&image)))
+            .map(|(index, direction, image)| {
+                let connections = sub_tile_paths.then(|| tile::get_connections(&image));
+                (index, direction, tile::get_edges(&image), connections)
+            })
             .collect()
     }
 
-    fn load_config(&mut self, config: &Config) {
+    #[doc(hidden)]
+    pub fn load_config(&mut self, config: &Config) {
         self.variants.clear();
 
         let neighbors = if let Some((image, tile_size)) = &config.image {
-            self.neighbors_from_image(image, *tile_size)
+            Self::neighbors_from_image(image, *tile_size, config.sub_tile_paths)
         } else {
             vec![]
         };
 
-        for (asset, direction, edges) in neighbors {
+        let mut sockets: HashMap<Vec<u8>, usize> = HashMap::new();
+        for (asset, direction, edges, connections) in neighbors {
+            let sockets = Direction::ALL.map(|d| {
+                let next = sockets.len();
+                *sockets.entry(edges.get(&d).clone()).or_insert(next)
+            });
+
             self.variants.push(Tile {
                 asset,
                 direction,
                 edges,
                 weight: 1.0,
-                path: Path::None,
+                cost: Some(1),
+                connections,
+                sockets,
             });
         }
 
         for variant in config.variants.iter() {
-            if let Some(existing) = self.variants.iter_mut().find(|v| v.asset == variant.index) {
+            for existing in self.variants.iter_mut().filter(|v| v.asset == variant.index) {
                 existing.weight = variant.weight;
+                existing.cost = variant.cost;
+                if let Some(groups) = &variant.connections {
+                    existing.connections = Some(tile::rotate_connections(groups, &existing.direction));
+                }
             }
         }
 
+        self.rules = config.rules.clone();
+        self.entrance_assets = config.variants.iter().filter(|v| v.entrance).map(|v| v.index).collect();
+        self.exit_assets = config.variants.iter().filter(|v| v.exit).map(|v| v.index).collect();
+        self.open_ratio = config.open_ratio;
+        self.weight_rules = config
+            .variants
+            .iter()
+            .filter(|v| !v.weight_rules.is_empty())
+            .map(|v| (v.index, v.weight_rules.clone()))
+            .collect();
+        self.limits = config
+            .variants
+            .iter()
+            .filter(|v| v.min.is_some() || v.max.is_some())
+            .map(|v| (v.index, v.min.unwrap_or(0)..=v.max.unwrap_or(usize::MAX)))
+            .collect();
+
         if self.variants.is_empty() {
             panic!("No variants set for map!");
         }
+
+        self.compatibility = Compatibility::new(self);
     }
 
-    fn generate_map(&mut self, rng: &mut ThreadRng, step_by_step: bool) -> bool {
-        let mut grid = self.clear(rng);
+    /// Collapses the empty cells, in `region` if given, and returns the filled grid.
+    #[doc(hidden)]
+    pub fn generate_map(
+        &mut self,
+        rng: &mut impl Rng,
+        mut grid: Grid,
+        entrance: &Position,
+        region: Option<&Rect>,
+        step_by_step: bool,
+    ) -> Result<Grid, Failure> {
+        self.history.constraints = self.constraints.clone();
         if step_by_step {
-            self.history.push(grid.clone());
+            self.history.push(
+                Step {
+                    position: Some(*entrance),
+                    ..Step::new(StepKind::Seed)
+                },
+                &grid,
+            );
         }
 
+        let mut counts = grid.count_assets(&self.variants);
+        let in_region = |position: &Position| region.is_none_or(|rect| rect.contains(position));
+
         loop {
-            if grid.tiles.iter().all(|tile| tile.is_some()) {
-                self.history.push(grid);
-                return true;
+            let empty: Vec<Position> = (0..grid.tiles.len())
+                .map(|index| (index % grid.size, index / grid.size))
+                .filter(|position| grid.get(position).is_none() && in_region(position))
+                .collect();
+
+            if empty.is_empty() {
+                if !self.meets_minimum(&counts) && !self.repair(rng, &mut grid, entrance, region, &mut counts, step_by_step) {
+                    if step_by_step {
+                        self.history.push(Step::new(StepKind::Contradiction), &grid);
+                    }
+                    return Err(Failure::Minimum);
+                }
+
+                return Ok(grid);
+            }
+
+            let mut free_neighbors = self.free_neighbors(&grid, region);
+
+            // Nothing to grow from, start over from any empty cell.
+            if free_neighbors.is_empty() {
+                free_neighbors = empty
+                    .iter()
+                    .map(|position| (*position, self.region_variants(&grid, position, region)))
+                    .collect();
             }
 
-            let free_neighbors = self.get_free_neighbors(&grid);
+            for (_, variants) in free_neighbors.iter_mut() {
+                variants.retain(|v| !self.is_exhausted(&counts, self.variants[*v].asset));
+            }
             let least_entropy = free_neighbors
                 .iter()
-                .min_by(|(_, a_tile), (_, b_tile)| {
-                    let a_sum: f32 = a_tile.iter().map(|a| self.variants[*a].weight).sum();
-                    let b_sum: f32 = b_tile.iter().map(|b| self.variants[*b].weight).sum();
+                .min_by(|(a_pos, a_tile), (b_pos, b_tile)| {
+                    let a_sum: f32 = a_tile.iter().map(|a| self.weight(&grid, a_pos, *a, entrance)).sum();
+                    let b_sum: f32 = b_tile.iter().map(|b| self.weight(&grid, b_pos, *b, entrance)).sum();
                     a_sum.partial_cmp(&b_sum).unwrap()
                 })
                 .map(|(_, tile)| tile);
@@ -226,35 +459,179 @@ impl Map {
             if let Some(least_entropy) = least_entropy {
                 let possibilties: Vec<&(Position, Vec<usize>)> = free_neighbors
                     .iter()
-                    .filter(|(position, _)| self.get_possible_variants(&grid, position).len() == least_entropy.len())
+                    .filter(|(_, variants)| variants.len() == least_entropy.len())
                     .collect();
 
                 let (next_pos, next_tile) = possibilties[rng.gen_range(0..possibilties.len())];
-                if next_tile.is_empty() {
+                let open_ratio = grid.open_ratio(&self.variants);
+                let weights: Vec<f32> = next_tile
+                    .iter()
+                    .map(|v| self.weight(&grid, next_pos, *v, entrance) * self.density_factor(&self.variants[*v], open_ratio))
+                    .collect();
+
+                let Some(variant) = self.weighted_variant(rng, next_tile, &weights) else {
+                    if step_by_step {
+                        self.history.push(
+                            Step {
+                                position: Some(*next_pos),
+                                candidates: next_tile.len(),
+                                ..Step::new(StepKind::Contradiction)
+                            },
+                            &grid,
+                        );
+                    }
+                    return Err(Failure::Contradiction(*next_pos));
+                };
+
+                *counts.entry(self.variants[variant].asset).or_default() += 1;
+                grid.set(next_pos, Some(Cell::new(variant)));
+
+                if step_by_step {
+                    self.history.push(
+                        Step {
+                            position: Some(*next_pos),
+                            candidates: next_tile.len(),
+                            entropy: history::entropy(&weights),
+                            ..Step::new(StepKind::Collapse)
+                        },
+                        &grid,
+                    );
+                }
+            } else {
+                return Err(Failure::Contradiction(empty[0]));
+            }
+        }
+    }
+
+    fn is_exhausted(&self, counts: &HashMap<usize, usize>, asset: usize) -> bool {
+        self.limits
+            .get(&asset)
+            .is_some_and(|limit| counts.get(&asset).copied().unwrap_or(0) >= *limit.end())
+    }
+
+    fn meets_minimum(&self, counts: &HashMap<usize, usize>) -> bool {
+        self.limits
+            .iter()
+            .all(|(asset, limit)| counts.get(asset).copied().unwrap_or(0) >= *limit.start())
+    }
+
+    /// Swaps tiles for assets that are below their minimum count wherever they fit, in `region` if given, returns false if that is
+    /// not possible.
+    fn repair(
+        &mut self,
+        rng: &mut impl Rng,
+        grid: &mut Grid,
+        entrance: &Position,
+        region: Option<&Rect>,
+        counts: &mut HashMap<usize, usize>,
+        step_by_step: bool,
+    ) -> bool {
+        let mut missing: Vec<usize> = self
+            .limits
+            .iter()
+            .filter(|(asset, limit)| counts.get(asset).copied().unwrap_or(0) < *limit.start())
+            .map(|(asset, _)| *asset)
+            .collect();
+        missing.sort();
+
+        for asset in missing {
+            while counts.get(&asset).copied().unwrap_or(0) < *self.limits[&asset].start() {
+                let mut candidates = vec![];
+
+                for index in 0..grid.tiles.len() {
+                    let position = (index % grid.size, index / grid.size);
+                    let Some(current) = *grid.get(&position) else {
+                        continue;
+                    };
+                    if position == *entrance || region.is_some_and(|rect| !rect.contains(&position)) {
+                        continue;
+                    }
+
+                    let current_asset = self.variants[current.variant].asset;
+                    let current_count = counts.get(&current_asset).copied().unwrap_or(0);
+                    if current_asset == asset || self.limits.get(&current_asset).is_some_and(|l| current_count <= *l.start()) {
+                        continue;
+                    }
+
+                    grid.set(&position, None);
+                    for variant in self.region_variants(grid, &position, region) {
+                        if self.variants[variant].asset == asset {
+                            candidates.push((position, variant));
+                        }
+                    }
+                    grid.set(&position, Some(current));
+                }
+
+                if candidates.is_empty() {
                     return false;
                 }
 
-                grid.set(next_pos, Some(self.weighted_variant(rng, next_tile)));
+                let (position, variant) = candidates[rng.gen_range(0..candidates.len())];
+                let previous = grid.tile(&position, &self.variants).unwrap().asset;
+                *counts.get_mut(&previous).unwrap() -= 1;
+                *counts.entry(asset).or_default() += 1;
+                grid.set(&position, Some(Cell::new(variant)));
 
                 if step_by_step {
-                    self.history.push(grid.clone());
+                    self.history.push(
+                        Step {
+                            position: Some(position),
+                            candidates: candidates.len(),
+                            ..Step::new(StepKind::Backtrack)
+                        },
+                        grid,
+                    );
                 }
-            } else {
-                return false;
             }
         }
+
+        true
     }
 
-    fn weighted_variant(&self, rng: &mut ThreadRng, variants: &[usize]) -> Tile {
-        let weights: Vec<f32> = variants.iter().map(|v| self.variants[*v].weight).collect();
-        let dist = WeightedIndex::new(&weights).unwrap();
+    fn weighted_variant(&self, rng: &mut impl Rng, variants: &[usize], weights: &[f32]) -> Option<usize> {
+        let dist = WeightedIndex::new(weights).ok()?;
 
-        self.variants[variants[dist.sample(rng)]].clone()
+        Some(variants[dist.sample(rng)])
+    }
+
+    /// Pushes the share of passable tiles towards `open_ratio` by favoring open or solid tiles.
+    fn density_factor(&self, tile: &Tile, achieved: f32) -> f32 {
+        let Some(target) = self.open_ratio else {
+            return 1.0;
+        };
+
+        let factor = if tile.is_open() {
+            target / achieved.max(0.01)
+        } else {
+            (1.0 - target) / (1.0 - achieved).max(0.01)
+        };
+        factor.clamp(0.01, 100.0)
+    }
+
+    /// Weight of a variant at the given position, after applying the weight rules of its asset.
+    pub fn weight(&self, grid: &Grid, position: &Position, variant: usize, entrance: &Position) -> f32 {
+        let tile = &self.variants[variant];
+        let Some(rules) = self.weight_rules.get(&tile.asset) else {
+            return tile.weight;
+        };
+
+        let context = WeightContext {
+            grid,
+            position,
+            tile,
+            entrance,
+            tileset: &self.variants,
+        };
+        rules.iter().fold(tile.weight, |weight, rule| weight * rule.factor(&context))
     }
 
     pub fn get_free_neighbors(&self, grid: &Grid) -> Vec<(Position, Vec<usize>)> {
+        self.free_neighbors(grid, None)
+    }
+
+    /// Empty cells next to placed tiles, in `region` if given, with the variants that fit in them.
+    fn free_neighbors(&self, grid: &Grid, region: Option<&Rect>) -> Vec<(Position, Vec<usize>)> {
         (0..grid.tiles.len())
-            .into_iter()
             .filter(|index| grid.tiles[*index].is_some())
             .flat_map(|index| {
                 let pos = &(index % grid.size, index / grid.size);
@@ -266,8 +643,8 @@ impl Map {
                 ];
 
                 neighbors.into_iter().flatten().filter_map(|position| {
-                    if grid.get(&position).is_none() {
-                        Some((position, self.get_possible_variants(grid, &position)))
+                    if grid.get(&position).is_none() && region.is_none_or(|rect| rect.contains(&position)) {
+                        Some((position, self.region_variants(grid, &position, region)))
                     } else {
                         None
                     }
@@ -277,62 +654,164 @@ impl Map {
             .collect()
     }
 
-    fn clear(&mut self, rng: &mut ThreadRng) -> Grid {
+    /// Creates an empty grid with a single entrance tile to grow the map from.
+    #[doc(hidden)]
+    pub fn clear(&self, rng: &mut impl Rng, entrance: &Position) -> Option<Grid> {
         let mut grid = Grid::new(self.size);
-        grid.tiles[rng.gen_range(0..(grid.size * grid.size))] = Some(self.variants[rng.gen_range(0..self.variants.len())].clone());
-        grid
+        self.seed(rng, &mut grid, entrance, None)?;
+        Some(grid)
     }
 
-    fn get_possible_variants(&self, grid: &Grid, position: &grid::Position) -> Vec<usize> {
-        let variants = self
-            .variants
-            .iter()
-            .enumerate()
-            .filter_map(|(variant_index, variant)| {
-                if let Some(north) = grid.move_position(position, Direction::North) {
-                    if let Some(tile) = grid.get(&north) {
-                        if variant.edges.north != tile.edges.south {
-                            return None;
-                        }
-                    }
-                } else if variant.edges.north.iter().any(|e| e > &0) {
-                    return None;
-                }
+    /// Places an entrance tile at the given position.
+    fn seed(&self, rng: &mut impl Rng, grid: &mut Grid, entrance: &Position, region: Option<&Rect>) -> Option<()> {
+        let variants = self.entrance_variants(grid, entrance, region);
+        let weights: Vec<f32> = variants.iter().map(|v| self.weight(grid, entrance, *v, entrance)).collect();
+        let variant = self.weighted_variant(rng, &variants, &weights)?;
+        grid.set(entrance, Some(Cell::new(variant)));
+        Some(())
+    }
 
-                if let Some(east) = grid.move_position(position, Direction::East) {
-                    if let Some(tile) = grid.get(&east) {
-                        if variant.edges.east != tile.edges.west {
-                            return None;
-                        }
-                    }
-                } else if variant.edges.east.iter().any(|e| e > &0) {
-                    return None;
-                }
+    fn entrance_variants(&self, grid: &Grid, entrance: &Position, region: Option<&Rect>) -> Vec<usize> {
+        let assets = &self.entrance_assets;
+        self.region_variants(grid, entrance, region)
+            .into_iter()
+            .filter(|v| assets.is_empty() || assets.contains(&self.variants[*v].asset))
+            .collect()
+    }
 
-                if let Some(south) = grid.move_position(position, Direction::South) {
-                    if let Some(tile) = grid.get(&south) {
-                        if variant.edges.south != tile.edges.north {
-                            return None;
-                        }
-                    }
-                } else if variant.edges.south.iter().any(|e| e > &0) {
-                    return None;
-                }
+    /// Whether an entrance tile can be placed at the position of an empty map. Expects the config to be loaded.
+    pub fn fits_entrance(&self, entrance: &Position) -> bool {
+        let grid = Grid::new(self.size);
+        entrance.0 < self.size
+            && entrance.1 < self.size
+            && self
+                .entrance_variants(&grid, entrance, None)
+                .iter()
+                .any(|v| self.weight(&grid, entrance, *v, entrance) > 0.0)
+    }
 
-                if let Some(west) = grid.move_position(position, Direction::West) {
-                    if let Some(tile) = grid.get(&west) {
-                        if variant.edges.west != tile.edges.east {
-                            return None;
+    pub fn get_possible_variants(&self, grid: &Grid, position: &grid::Position) -> Vec<usize> {
+        self.get_possible_variants_with(grid, position, &self.constraints)
+    }
+
+    /// Like `get_possible_variants`, under the given constraints instead of the map's, such as those of a `History`.
+    pub fn get_possible_variants_with(&self, grid: &Grid, position: &Position, constraints: &[Option<Constraint>]) -> Vec<usize> {
+        self.candidates(grid, position, constraints, None).iter().collect()
+    }
+
+    /// Variants that fit at the position while regenerating `region`, if given.
+    fn region_variants(&self, grid: &Grid, position: &Position, region: Option<&Rect>) -> Vec<usize> {
+        self.candidates(grid, position, &self.constraints, region).iter().collect()
+    }
+
+    /// Cells whose tile conflicts with its neighbors or constraints, and empty cells no variant fits in.
+    pub fn impossible_cells(&self, grid: &Grid) -> Vec<Position> {
+        (0..grid.tiles.len())
+            .map(|index| (index % grid.size, index / grid.size))
+            .filter(|position| match grid.get(position) {
+                Some(cell) => !self.candidates(grid, position, &self.constraints, None).contains(cell.variant),
+                None => self.get_possible_variants(grid, position).is_empty(),
+            })
+            .collect()
+    }
+
+    /// Variants that may be placed at the position, given its constraint and neighbors. While regenerating `region`, the
+    /// empty cells around it were pruned and are closed off like the border.
+    fn candidates(&self, grid: &Grid, position: &Position, constraints: &[Option<Constraint>], region: Option<&Rect>) -> Bitset {
+        let mut candidates = Bitset::full(self.variants.len());
+
+        for direction in Direction::ALL {
+            let d = direction.clone() as usize;
+            let index = match direction {
+                Direction::North | Direction::South => position.0,
+                Direction::East | Direction::West => position.1,
+            };
+
+            match grid.move_position(position, direction) {
+                Some(neighbor) => match grid.get(&neighbor) {
+                    Some(cell) => candidates.intersect_with(&self.compatibility.neighbors[cell.variant][d]),
+                    None if region.is_some_and(|rect| !rect.contains(&neighbor)) => {
+                        candidates.intersect_with(&self.compatibility.border[d])
+                    }
+                    None => {}
+                },
+                None => match &self.border {
+                    Border::Closed => candidates.intersect_with(&self.compatibility.border[d]),
+                    Border::Neighbors(sides) => {
+                        if let Some(side) = &sides[d] {
+                            candidates.intersect_with(&self.compatibility.neighbors[side[index]][d]);
                         }
                     }
-                } else if variant.edges.west.iter().any(|e| e > &0) {
-                    return None;
-                }
+                },
+            }
+        }
 
-                Some(variant_index)
-            })
-            .collect();
+        if let Some(Some(constraint)) = constraints.get(position.1 * grid.size + position.0) {
+            let mut allowed = Bitset::new(self.variants.len());
+            for variant in candidates.iter().filter(|v| constraint.allows(&self.variants[*v])) {
+                allowed.insert(variant);
+            }
+            candidates = allowed;
+        }
 
-        variants
+        candidates
+    }
+
+    /// Checks the grid against the loaded tileset like `Grid::validate`, with the adjacency rules of the map. Sides facing
+    /// the border are only checked when it is closed.
+    pub fn validate(&self, grid: &Grid) -> Vec<Violation> {
+        grid.validate_with(
+            &self.variants,
+            |variant, neighbor, direction| self.fits(variant, neighbor, direction),
+            |variant, direction| !matches!(self.border, Border::Closed) || self.fits_border(variant, direction),
+        )
+    }
+
+    /// Whether `neighbor` may be placed on the `direction` side of `variant`.
+    pub fn fits(&self, variant: &Tile, neighbor: &Tile, direction: &Direction) -> bool {
+        match &self.rules {
+            Some(rules) => rules.fits(&variant.key(), &neighbor.key(), direction),
+            None => variant.fits(neighbor, direction),
+        }
+    }
+
+    /// Whether `variant` may be placed with its `direction` side facing the border.
+    pub fn fits_border(&self, variant: &Tile, direction: &Direction) -> bool {
+        match &self.rules {
+            Some(rules) => rules.fits_border(&variant.key(), direction),
+            None => !variant.edges.is_open(direction),
+        }
+    }
+}
+
+/// Runs `attempt` until it succeeds, at most `tries` times, and returns the last failure otherwise.
+fn retry_bounded<T>(tries: usize, mut attempt: impl FnMut() -> Result<T, Failure>) -> Result<T, Failure> {
+    let mut result = attempt();
+    for _ in 1..tries {
+        if result.is_ok() {
+            break;
+        }
+        result = attempt();
+    }
+
+    result
+}
+
+/// Runs `attempt` until it succeeds and reports how long it took.
+fn retry<T>(name: &str, mut attempt: impl FnMut() -> Option<T>) -> T {
+    let time = Instant::now();
+    let mut tries = 0;
+
+    loop {
+        tries += 1;
+
+        if let Some(result) = attempt() {
+            let elapsed = time.elapsed().as_secs_f32();
+
+            println!("{} generated after {} tries", name, tries);
+            println!("Time taken: {}", elapsed);
+            println!("Per try: {}", elapsed / tries as f32);
+            return result;
+        }
     }
 }
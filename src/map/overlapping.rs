@@ -0,0 +1,253 @@
+use super::grid::Direction;
+use image::{DynamicImage, Rgba, RgbaImage};
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::Rng;
+use std::collections::HashMap;
+
+type Pattern = Vec<Rgba<u8>>;
+
+/// Overlapping model, synthesizes images that are locally similar to a sample bitmap.
+#[derive(Debug)]
+pub struct Overlapping {
+    pub width: usize,
+    pub height: usize,
+    /// Size of the square patterns extracted from the sample.
+    pub n: usize,
+    /// Wrap the sample around its edges when extracting patterns.
+    pub periodic_input: bool,
+    /// Also extract rotated and reflected patterns.
+    pub symmetry: bool,
+    pub history: Vec<RgbaImage>,
+    patterns: Vec<Pattern>,
+    weights: Vec<f32>,
+    propagator: Vec<[Vec<usize>; 4]>,
+}
+
+impl Overlapping {
+    pub fn new(width: usize, height: usize, n: usize) -> Self {
+        Self {
+            width,
+            height,
+            n,
+            periodic_input: true,
+            symmetry: true,
+            history: vec![],
+            patterns: vec![],
+            weights: vec![],
+            propagator: vec![],
+        }
+    }
+
+    pub fn build(&mut self, rng: &mut impl Rng, sample: &DynamicImage, log_history: bool) {
+        self.load_sample(sample);
+
+        // Frames of abandoned attempts are dropped, so the history only shows the successful one.
+        super::retry("Image", || {
+            self.history.clear();
+            self.generate(rng, log_history)
+        });
+    }
+
+    fn load_sample(&mut self, sample: &DynamicImage) {
+        let sample = sample.to_rgba8();
+        let (width, height) = (sample.width() as usize, sample.height() as usize);
+        let n = self.n;
+
+        let (max_x, max_y) = if self.periodic_input {
+            (width, height)
+        } else {
+            ((width + 1).saturating_sub(n), (height + 1).saturating_sub(n))
+        };
+
+        let mut indices: HashMap<Pattern, usize> = HashMap::new();
+        self.patterns.clear();
+        self.weights.clear();
+
+        for y in 0..max_y {
+            for x in 0..max_x {
+                let pattern: Pattern = (0..n * n)
+                    .map(|i| *sample.get_pixel(((x + i % n) % width) as u32, ((y + i / n) % height) as u32))
+                    .collect();
+
+                let mut symmetries = vec![pattern];
+                if self.symmetry {
+                    for i in 1..8 {
+                        let next = if i % 2 == 1 {
+                            reflect(&symmetries[i - 1], n)
+                        } else {
+                            rotate(&symmetries[i - 2], n)
+                        };
+                        symmetries.push(next);
+                    }
+                }
+
+                for pattern in symmetries {
+                    let index = *indices.entry(pattern.clone()).or_insert_with(|| {
+                        self.patterns.push(pattern);
+                        self.weights.push(0.0);
+                        self.patterns.len() - 1
+                    });
+                    self.weights[index] += 1.0;
+                }
+            }
+        }
+
+        if self.patterns.is_empty() {
+            panic!("Sample is smaller than the pattern size!");
+        }
+
+        self.propagator = self
+            .patterns
+            .iter()
+            .map(|a| {
+                Direction::ALL.map(|direction| {
+                    let (dx, dy) = offset(&direction);
+                    (0..self.patterns.len())
+                        .filter(|b| agrees(a, &self.patterns[*b], dx, dy, n))
+                        .collect()
+                })
+            })
+            .collect();
+    }
+
+    fn generate(&mut self, rng: &mut impl Rng, step_by_step: bool) -> Option<()> {
+        let mut wave = vec![vec![true; self.patterns.len()]; self.width * self.height];
+        if step_by_step {
+            self.history.push(self.render(&wave));
+        }
+
+        loop {
+            let mut least_entropy = None;
+            for (index, cell) in wave.iter().enumerate() {
+                let options: Vec<usize> = (0..cell.len()).filter(|t| cell[*t]).collect();
+                match options.len() {
+                    0 => return None,
+                    1 => continue,
+                    _ => {}
+                }
+
+                let sum: f32 = options.iter().map(|t| self.weights[*t]).sum();
+                let log_sum: f32 = options.iter().map(|t| self.weights[*t] * self.weights[*t].ln()).sum();
+                let entropy = sum.ln() - log_sum / sum + rng.gen_range(0.0..1e-6);
+
+                if least_entropy.is_none_or(|(_, e)| entropy < e) {
+                    least_entropy = Some((index, entropy));
+                }
+            }
+
+            let Some((index, _)) = least_entropy else {
+                self.history.push(self.render(&wave));
+                return Some(());
+            };
+
+            let options: Vec<usize> = (0..self.patterns.len()).filter(|t| wave[index][*t]).collect();
+            let dist = WeightedIndex::new(options.iter().map(|t| self.weights[*t])).unwrap();
+            let chosen = options[dist.sample(rng)];
+            wave[index].iter_mut().enumerate().for_each(|(t, allowed)| *allowed = t == chosen);
+
+            if !self.propagate(&mut wave, index) {
+                return None;
+            }
+
+            if step_by_step {
+                self.history.push(self.render(&wave));
+            }
+        }
+    }
+
+    /// Removes patterns that no longer agree with their neighbors, returns false on a contradiction.
+    fn propagate(&self, wave: &mut [Vec<bool>], start: usize) -> bool {
+        let mut stack = vec![start];
+
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % self.width, index / self.width);
+
+            for (d, direction) in Direction::ALL.iter().enumerate() {
+                let (dx, dy) = offset(direction);
+                let nx = (x as i32 + dx).rem_euclid(self.width as i32) as usize;
+                let ny = (y as i32 + dy).rem_euclid(self.height as i32) as usize;
+                let neighbor = ny * self.width + nx;
+
+                let mut supported = vec![false; self.patterns.len()];
+                for t in (0..self.patterns.len()).filter(|t| wave[index][*t]) {
+                    for s in self.propagator[t][d].iter() {
+                        supported[*s] = true;
+                    }
+                }
+
+                let mut changed = false;
+                for (allowed, supported) in wave[neighbor].iter_mut().zip(supported) {
+                    if *allowed && !supported {
+                        *allowed = false;
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    if !wave[neighbor].iter().any(|allowed| *allowed) {
+                        return false;
+                    }
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Colors each pixel by the weighted average of its remaining patterns.
+    fn render(&self, wave: &[Vec<bool>]) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+
+        for (index, cell) in wave.iter().enumerate() {
+            let mut color = [0.0; 4];
+            let mut total = 0.0;
+
+            for t in (0..cell.len()).filter(|t| cell[*t]) {
+                for (c, value) in color.iter_mut().zip(self.patterns[t][0].0) {
+                    *c += value as f32 * self.weights[t];
+                }
+                total += self.weights[t];
+            }
+
+            if total > 0.0 {
+                let pixel = Rgba(color.map(|c| (c / total) as u8));
+                image.put_pixel((index % self.width) as u32, (index / self.width) as u32, pixel);
+            }
+        }
+
+        image
+    }
+}
+
+fn offset(direction: &Direction) -> (i32, i32) {
+    match direction {
+        Direction::North => (0, -1),
+        Direction::East => (1, 0),
+        Direction::South => (0, 1),
+        Direction::West => (-1, 0),
+    }
+}
+
+/// Whether pattern `b` placed at offset `(dx, dy)` from pattern `a` overlaps it without conflicts.
+fn agrees(a: &Pattern, b: &Pattern, dx: i32, dy: i32, n: usize) -> bool {
+    let n = n as i32;
+    for y in dy.max(0)..(n + dy).min(n) {
+        for x in dx.max(0)..(n + dx).min(n) {
+            if a[(y * n + x) as usize] != b[((y - dy) * n + (x - dx)) as usize] {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+fn rotate(pattern: &Pattern, n: usize) -> Pattern {
+    (0..n * n).map(|i| pattern[(n - 1 - i % n) * n + i / n]).collect()
+}
+
+fn reflect(pattern: &Pattern, n: usize) -> Pattern {
+    (0..n * n).map(|i| pattern[(i / n) * n + n - 1 - i % n]).collect()
+}
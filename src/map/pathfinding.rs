@@ -1,28 +1,29 @@
 use super::grid::{self, Direction, Grid, Position};
-use pathfinding::prelude::astar;
+use super::Tile;
+use pathfinding::prelude::{astar, bfs_reach, dijkstra_all};
+use std::collections::{HashMap, HashSet};
 
-fn get_successors(grid: &Grid, position: &grid::Position) -> Vec<Position> {
+/// A position and the side it was entered through, if that limits where the tile can be left.
+type Node = (Position, Option<Direction>);
+
+/// Neighbors that can be walked onto from the node, with the cost of doing so.
+fn get_successors(grid: &Grid, tileset: &[Tile], (position, entered): &Node) -> Vec<(Node, usize)> {
     let mut successors = Vec::new();
 
-    if let Some(tile) = grid.get(position) {
-        if tile.edges.north.iter().any(|e| e > &0) {
-            if let Some(next) = grid.move_position(position, Direction::North) {
-                successors.push(next);
-            }
-        }
-        if tile.edges.east.iter().any(|e| e > &0) {
-            if let Some(next) = grid.move_position(position, Direction::East) {
-                successors.push(next);
-            }
-        }
-        if tile.edges.south.iter().any(|e| e > &0) {
-            if let Some(next) = grid.move_position(position, Direction::South) {
-                successors.push(next);
+    if let Some(tile) = grid.tile(position, tileset) {
+        for direction in Direction::ALL {
+            if !tile.edges.is_open(&direction) || entered.as_ref().is_some_and(|side| !tile.joins(side, &direction)) {
+                continue;
             }
-        }
-        if tile.edges.west.iter().any(|e| e > &0) {
-            if let Some(next) = grid.move_position(position, Direction::West) {
-                successors.push(next);
+
+            if let Some(next) = grid.move_position(position, direction.clone()) {
+                let Some(next_tile) = grid.tile(&next, tileset) else {
+                    continue;
+                };
+                if let Some(cost) = next_tile.cost {
+                    let side = next_tile.connections.is_some().then(|| direction.opposite());
+                    successors.push(((next, side), cost));
+                }
             }
         }
     }
@@ -34,13 +35,115 @@ fn distance((px, py): grid::Position, (gx, gy): grid::Position) -> usize {
     ((px as i32 - gx as i32).abs() + (py as i32 - gy as i32).abs()) as usize
 }
 
-pub fn test(grid: &Grid, entrance: grid::Position, exit: grid::Position) -> Option<(Vec<grid::Position>, usize)> {
-    let result = astar(
-        &entrance,
-        |p| get_successors(grid, p).iter().map(|s| (*s, 1)).collect::<Vec<_>>(),
-        |p| distance(*p, exit),
-        |p| *p == exit,
-    );
+/// Cheapest path from the entrance to the exit and its cost.
+pub fn test(grid: &Grid, tileset: &[Tile], entrance: grid::Position, exit: grid::Position) -> Option<(Vec<grid::Position>, usize)> {
+    // Every step costs at least this much, which keeps the heuristic from overestimating.
+    let min_cost = tileset.iter().filter_map(|tile| tile.cost).min().unwrap_or(1);
+
+    let (nodes, cost) = astar(
+        &(entrance, None),
+        |node| get_successors(grid, tileset, node),
+        |(p, _)| distance(*p, exit) * min_cost,
+        |(p, _)| *p == exit,
+    )?;
+
+    Some((nodes.into_iter().map(|(p, _)| p).collect(), cost))
+}
+
+/// Cells that can be walked to from `start`, only walking onto cells `allowed` accepts.
+pub fn reachable(grid: &Grid, tileset: &[Tile], start: Position, allowed: impl Fn(&Position) -> bool) -> HashSet<Position> {
+    bfs_reach((start, None), |node| {
+        get_successors(grid, tileset, node)
+            .into_iter()
+            .map(|(next, _)| next)
+            .filter(|(position, _)| allowed(position))
+    })
+    .map(|(position, _)| position)
+    .collect()
+}
+
+/// Nodes the node can be walked onto from, with the cost of doing so. The reverse of `get_successors`.
+fn get_predecessors(grid: &Grid, tileset: &[Tile], (position, entered): &Node) -> Vec<(Node, usize)> {
+    let mut predecessors = Vec::new();
+    let Some(tile) = grid.tile(position, tileset) else {
+        return predecessors;
+    };
+    let Some(cost) = tile.cost else {
+        return predecessors;
+    };
+
+    for side in Direction::ALL {
+        // Tiles with connections remember the side they were entered through.
+        if *entered != tile.connections.is_some().then(|| side.clone()) {
+            continue;
+        }
+
+        let Some(previous) = grid.move_position(position, side.clone()) else {
+            continue;
+        };
+        let Some(previous_tile) = grid.tile(&previous, tileset) else {
+            continue;
+        };
+        let direction = side.opposite();
+        if !previous_tile.edges.is_open(&direction) {
+            continue;
+        }
+
+        predecessors.push(((previous, None), cost));
+        if previous_tile.connections.is_some() {
+            for from in Direction::ALL.into_iter().filter(|from| previous_tile.joins(from, &direction)) {
+                predecessors.push(((previous, Some(from)), cost));
+            }
+        }
+    }
+
+    predecessors
+}
+
+/// Cheapest walks from every node to any of the sources, searched backwards from the sources. Each node maps to the
+/// next node of its walk, `None` at the sources, and the cost of the walk.
+fn walks(grid: &Grid, tileset: &[Tile], sources: &[Position]) -> HashMap<Option<Node>, (Option<Node>, usize)> {
+    // Walks end at a node before the sources, so that they are all searched at once. Reaching a source through any
+    // side ends the walk.
+    dijkstra_all(&None, |node: &Option<Node>| match node {
+        None => sources
+            .iter()
+            .flat_map(|source| {
+                std::iter::once(None)
+                    .chain(Direction::ALL.map(Some))
+                    .map(|side| (Some((*source, side)), 0))
+            })
+            .collect(),
+        Some(node) => get_predecessors(grid, tileset, node)
+            .into_iter()
+            .map(|(previous, cost)| (Some(previous), cost))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Cost of the cheapest walk from every cell to any of the sources, indexed like `Grid::tiles`.
+pub fn distances(grid: &Grid, tileset: &[Tile], sources: &[Position]) -> Vec<Option<usize>> {
+    let walks = walks(grid, tileset, sources);
+
+    (0..grid.tiles.len())
+        .map(|index| {
+            let position = (index % grid.size, index / grid.size);
+            walks.get(&Some((position, None))).map(|(_, cost)| *cost)
+        })
+        .collect()
+}
+
+/// First step of the cheapest walk from every cell to any of the sources, indexed like `Grid::tiles`.
+pub fn flow(grid: &Grid, tileset: &[Tile], sources: &[Position]) -> Vec<Option<Direction>> {
+    let walks = walks(grid, tileset, sources);
 
-    result
+    (0..grid.tiles.len())
+        .map(|index| {
+            let position = (index % grid.size, index / grid.size);
+            let (next, _) = walks.get(&Some((position, None)))?.0.clone()?;
+            Direction::ALL
+                .into_iter()
+                .find(|direction| grid.move_position(&position, direction.clone()) == Some(next))
+        })
+        .collect()
 }
@@ -0,0 +1,103 @@
+use super::grid::Direction;
+use super::{tile, Config, Variants};
+use image::DynamicImage;
+use std::collections::{HashMap, HashSet};
+
+/// A variant identified by its asset index and rotation.
+pub type VariantKey = (usize, Direction);
+
+/// A hand-authored layout, rows of `(asset, direction)` from north to south.
+pub type Example = Vec<Vec<VariantKey>>;
+
+/// Explicit adjacency rules, used instead of matching edges when set on a `Config`.
+#[derive(Debug, Default, Clone)]
+pub struct Rules {
+    /// `(variant, direction, neighbor)` triples where `neighbor` may be placed on the `direction` side of `variant`.
+    pub adjacent: HashSet<(VariantKey, Direction, VariantKey)>,
+    /// Variants that may be placed with the given side facing the border.
+    pub border: HashSet<(VariantKey, Direction)>,
+}
+
+impl Rules {
+    pub fn fits(&self, variant: &VariantKey, neighbor: &VariantKey, direction: &Direction) -> bool {
+        self.adjacent.contains(&(variant.clone(), direction.clone(), neighbor.clone()))
+    }
+
+    pub fn fits_border(&self, variant: &VariantKey, direction: &Direction) -> bool {
+        self.border.contains(&(variant.clone(), direction.clone()))
+    }
+}
+
+impl Config {
+    /// Learns adjacency rules and asset weights from example layouts, the way the simple tiled model does.
+    pub fn from_examples(image: DynamicImage, tile_size: u32, examples: &[Example]) -> Self {
+        let canonical = canonical_variants(&image, tile_size);
+        let key = |(asset, direction): &VariantKey| {
+            canonical
+                .get(&(*asset, direction.clone()))
+                .cloned()
+                .unwrap_or_else(|| panic!("Asset {} in example is not part of the tileset!", asset))
+        };
+
+        let mut rules = Rules::default();
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        let mut total = 0;
+
+        for example in examples {
+            for (y, row) in example.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    let variant = key(cell);
+                    *counts.entry(variant.0).or_default() += 1;
+                    total += 1;
+
+                    for direction in Direction::ALL {
+                        let neighbor = match direction {
+                            Direction::North => y.checked_sub(1).map(|y| (x, y)),
+                            Direction::East => Some((x + 1, y)),
+                            Direction::South => Some((x, y + 1)),
+                            Direction::West => x.checked_sub(1).map(|x| (x, y)),
+                        }
+                        .and_then(|(x, y)| example.get(y).and_then(|row| row.get(x)));
+
+                        match neighbor {
+                            Some(neighbor) => rules.adjacent.insert((variant.clone(), direction, key(neighbor))),
+                            None => rules.border.insert((variant.clone(), direction)),
+                        };
+                    }
+                }
+            }
+        }
+
+        let assets = canonical.keys().map(|(asset, _)| *asset).max().map_or(0, |max| max + 1);
+        let variants = (0..assets)
+            .map(|index| Variants {
+                index,
+                weight: counts.get(&index).map_or(0.0, |count| *count as f32 / total as f32),
+                ..Default::default()
+            })
+            .collect();
+
+        Self {
+            image: Some((image, tile_size)),
+            variants,
+            rules: Some(rules),
+            ..Default::default()
+        }
+    }
+}
+
+/// Maps every rotation of every asset to the rotation that is kept after identical rotations are deduplicated.
+fn canonical_variants(image: &DynamicImage, tile_size: u32) -> HashMap<VariantKey, VariantKey> {
+    let rotations = tile::get_rotations(image, tile_size);
+    let mut canonical = HashMap::new();
+
+    for (asset, direction, variant_img) in rotations.iter() {
+        let (_, first, _) = rotations
+            .iter()
+            .find(|(a, _, i)| a == asset && i.as_bytes() == variant_img.as_bytes())
+            .unwrap();
+        canonical.insert((*asset, direction.clone()), (*asset, first.clone()));
+    }
+
+    canonical
+}
@@ -1,5 +1,6 @@
 use super::grid::Direction;
-use image::GenericImageView;
+use super::rules::VariantKey;
+use image::{DynamicImage, GenericImageView};
 use std::hash::{Hash, Hasher};
 
 #[derive(Default, Debug, Clone, Hash)]
@@ -10,7 +11,25 @@ pub struct Edges {
     pub west: Vec<u8>,
 }
 
-#[derive(Debug, Clone)]
+impl Edges {
+    pub fn get(&self, direction: &Direction) -> &Vec<u8> {
+        match direction {
+            Direction::North => &self.north,
+            Direction::East => &self.east,
+            Direction::South => &self.south,
+            Direction::West => &self.west,
+        }
+    }
+
+    pub fn is_open(&self, direction: &Direction) -> bool {
+        self.get(direction).iter().any(|e| e > &0)
+    }
+}
+
+/// For every side of a tile, the sides it is joined to, both indexed by `Direction`.
+pub type Connections = [[bool; 4]; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Path {
     Entrance,
     Track,
@@ -18,18 +37,54 @@ pub enum Path {
     None,
 }
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct Pos(i32, i32);
-
 #[derive(Debug, Clone)]
 pub struct Tile {
     pub edges: Edges,
     pub asset: usize,
     pub direction: Direction,
     pub weight: f32,
+    /// Cost of walking onto the tile, `None` if it is impassable.
+    pub cost: Option<usize>,
+    /// Sides that are joined within the tile, `None` joins all open sides.
+    pub connections: Option<Connections>,
+    /// Interned edges indexed by `Direction`, equal edges share the same ID.
+    pub sockets: [usize; 4],
+}
+
+/// A placed variant, indexing into the tileset it was generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub variant: usize,
     pub path: Path,
 }
 
+impl Cell {
+    pub fn new(variant: usize) -> Self {
+        Self { variant, path: Path::None }
+    }
+}
+
+impl Tile {
+    pub fn key(&self) -> VariantKey {
+        (self.asset, self.direction.clone())
+    }
+
+    /// Whether any side has an open socket.
+    pub fn is_open(&self) -> bool {
+        Direction::ALL.iter().any(|direction| self.edges.is_open(direction))
+    }
+
+    /// Whether the tile can be left through `to` after entering it through `from`.
+    pub fn joins(&self, from: &Direction, to: &Direction) -> bool {
+        self.connections
+            .is_none_or(|connections| connections[from.clone() as usize][to.clone() as usize])
+    }
+
+    pub fn fits(&self, other: &Tile, direction: &Direction) -> bool {
+        self.sockets[direction.clone() as usize] == other.sockets[direction.opposite() as usize]
+    }
+}
+
 impl Hash for Tile {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.edges.hash(state);
@@ -55,3 +110,88 @@ pub fn get_edges(image: &image::DynamicImage) -> Edges {
 
     Edges { north, south, east, west }
 }
+
+/// Joins the sides whose open pixels are connected within the image.
+pub fn get_connections(image: &DynamicImage) -> Connections {
+    let (width, height) = image.dimensions();
+    let is_open = |x: u32, y: u32| image.get_pixel(x, y).0[0] > 0;
+
+    // Label the open areas with a flood fill.
+    let mut labels: Vec<Option<usize>> = vec![None; (width * height) as usize];
+    let mut areas = 0;
+    for start in 0..width * height {
+        if labels[start as usize].is_some() || !is_open(start % width, start / width) {
+            continue;
+        }
+
+        let mut stack = vec![(start % width, start / width)];
+        labels[start as usize] = Some(areas);
+        while let Some((x, y)) = stack.pop() {
+            let neighbors = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+            for (nx, ny) in neighbors {
+                if nx < width && ny < height && labels[(ny * width + nx) as usize].is_none() && is_open(nx, ny) {
+                    labels[(ny * width + nx) as usize] = Some(areas);
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        areas += 1;
+    }
+
+    let sides: [Vec<(u32, u32)>; 4] = [
+        (0..width).map(|x| (x, 0)).collect(),
+        (0..height).map(|y| (width - 1, y)).collect(),
+        (0..width).map(|x| (x, height - 1)).collect(),
+        (0..height).map(|y| (0, y)).collect(),
+    ];
+    let areas = sides.map(|pixels| {
+        pixels
+            .iter()
+            .filter_map(|(x, y)| labels[(y * width + x) as usize])
+            .collect::<Vec<usize>>()
+    });
+
+    let mut connections = [[false; 4]; 4];
+    for from in 0..4 {
+        for to in 0..4 {
+            connections[from][to] = areas[from].iter().any(|area| areas[to].contains(area));
+        }
+    }
+
+    connections
+}
+
+/// Joins the given groups of sides, declared for the unrotated asset, turned to face `direction`.
+pub fn rotate_connections(groups: &[Vec<Direction>], direction: &Direction) -> Connections {
+    let turns = direction.clone() as usize;
+    let mut connections = [[false; 4]; 4];
+
+    for group in groups {
+        for from in group {
+            for to in group {
+                connections[(from.clone() as usize + turns) % 4][(to.clone() as usize + turns) % 4] = true;
+            }
+        }
+    }
+
+    connections
+}
+
+pub fn get_rotations(image: &DynamicImage, tile_size: u32) -> Vec<(usize, Direction, DynamicImage)> {
+    let mut variants = vec![];
+    let columns = image.width() / tile_size;
+
+    for x in 0..columns {
+        for y in 0..(image.height() / tile_size) {
+            let index = (y * columns + x) as usize;
+            let mut variant_img = image.clone().crop(x * tile_size, y * tile_size, tile_size, tile_size);
+
+            for direction in Direction::ALL {
+                variants.push((index, direction, variant_img.clone()));
+                variant_img = variant_img.rotate90();
+            }
+        }
+    }
+
+    variants
+}
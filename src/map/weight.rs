@@ -0,0 +1,89 @@
+use super::grid::{Direction, Grid, Position};
+use super::Tile;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Everything known about a cell when one of its candidates is weighted.
+pub struct WeightContext<'a> {
+    pub grid: &'a Grid,
+    pub position: &'a Position,
+    pub tile: &'a Tile,
+    pub entrance: &'a Position,
+    /// Tiles the cells of `grid` refer to.
+    pub tileset: &'a [Tile],
+}
+
+impl WeightContext<'_> {
+    pub fn border_distance(&self) -> usize {
+        let (x, y) = *self.position;
+        let last = self.grid.size - 1;
+        x.min(y).min(last - x).min(last - y)
+    }
+
+    pub fn entrance_distance(&self) -> usize {
+        self.position.0.abs_diff(self.entrance.0) + self.position.1.abs_diff(self.entrance.1)
+    }
+
+    /// Assets already placed next to the cell.
+    pub fn neighbor_assets(&self) -> Vec<usize> {
+        Direction::ALL
+            .iter()
+            .filter_map(|direction| self.grid.move_position(self.position, direction.clone()))
+            .filter_map(|position| self.grid.tile(&position, self.tileset).map(|tile| tile.asset))
+            .collect()
+    }
+}
+
+/// Multiplies the weight of a variant depending on where it is about to be placed.
+#[derive(Clone)]
+pub enum WeightRule {
+    /// Applies the factor when any neighbor uses one of the assets.
+    Neighbor(Vec<usize>, f32),
+    /// Applies the factor when the distance to the closest border is in range.
+    Border(Range<usize>, f32),
+    /// Applies the factor when the Manhattan distance to the entrance is in range.
+    Entrance(Range<usize>, f32),
+    /// Multiplies the weight by the result of the callback.
+    Custom(Arc<dyn Fn(&WeightContext) -> f32 + Send + Sync>),
+}
+
+impl WeightRule {
+    pub fn factor(&self, context: &WeightContext) -> f32 {
+        match self {
+            WeightRule::Neighbor(assets, factor) => {
+                if context.neighbor_assets().iter().any(|asset| assets.contains(asset)) {
+                    *factor
+                } else {
+                    1.0
+                }
+            }
+            WeightRule::Border(range, factor) => {
+                if range.contains(&context.border_distance()) {
+                    *factor
+                } else {
+                    1.0
+                }
+            }
+            WeightRule::Entrance(range, factor) => {
+                if range.contains(&context.entrance_distance()) {
+                    *factor
+                } else {
+                    1.0
+                }
+            }
+            WeightRule::Custom(callback) => callback(context),
+        }
+    }
+}
+
+impl fmt::Debug for WeightRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightRule::Neighbor(assets, factor) => f.debug_tuple("Neighbor").field(assets).field(factor).finish(),
+            WeightRule::Border(range, factor) => f.debug_tuple("Border").field(range).field(factor).finish(),
+            WeightRule::Entrance(range, factor) => f.debug_tuple("Entrance").field(range).field(factor).finish(),
+            WeightRule::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use super::grid::{Border, Direction, Grid};
+use super::{Config, Failure, Map};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+pub type ChunkCoord = (i32, i32);
+
+/// An endless map generated one chunk at a time, seams between chunks always match.
+#[derive(Debug)]
+pub struct World {
+    pub seed: u64,
+    pub chunk_size: usize,
+    pub chunks: HashMap<ChunkCoord, Grid>,
+    /// Attempts at generating a chunk before giving up on it.
+    pub max_tries: usize,
+    map: Map,
+}
+
+impl World {
+    pub fn new(seed: u64, chunk_size: usize, config: &Config) -> Self {
+        let mut map = Map::new(chunk_size, 0..usize::MAX);
+        map.load_config(config);
+
+        Self {
+            seed,
+            chunk_size,
+            chunks: HashMap::new(),
+            max_tries: 1000,
+            map,
+        }
+    }
+
+    /// Returns the chunk at `coord`, generating it if needed. Fails if no attempt matched the neighboring chunks.
+    pub fn chunk(&mut self, coord: ChunkCoord) -> Result<&Grid, Failure> {
+        if !self.chunks.contains_key(&coord) {
+            let grid = self.generate(coord)?;
+            self.chunks.insert(coord, grid);
+        }
+
+        Ok(&self.chunks[&coord])
+    }
+
+    fn generate(&mut self, (cx, cy): ChunkCoord) -> Result<Grid, Failure> {
+        let size = self.chunk_size;
+        let last = size - 1;
+        let sides = Direction::ALL.map(|direction| {
+            let coord = match direction {
+                Direction::North => (cx, cy - 1),
+                Direction::East => (cx + 1, cy),
+                Direction::South => (cx, cy + 1),
+                Direction::West => (cx - 1, cy),
+            };
+            let neighbor = self.chunks.get(&coord)?;
+
+            let side = (0..size).map(|i| {
+                let position = match direction {
+                    Direction::North => (i, last),
+                    Direction::East => (0, i),
+                    Direction::South => (i, 0),
+                    Direction::West => (last, i),
+                };
+                neighbor.get(&position).unwrap().variant
+            });
+            Some(side.collect())
+        });
+
+        self.map.border = Border::Neighbors(sides);
+
+        let mut rng = StdRng::seed_from_u64(chunk_seed(self.seed, (cx, cy)));
+        let map = &mut self.map;
+
+        super::retry_bounded(self.max_tries, || {
+            let start = (rng.gen_range(0..size), rng.gen_range(0..size));
+            let grid = map.clear(&mut rng, &start).ok_or(Failure::Entrance(start))?;
+
+            map.generate_map(&mut rng, grid, &start, None, false)
+        })
+    }
+}
+
+fn chunk_seed(seed: u64, (x, y): ChunkCoord) -> u64 {
+    seed ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+}
@@ -0,0 +1,128 @@
+use dungeon_crawler_map::map::{Step, StepKind};
+use macroquad::prelude::*;
+use std::time::Instant;
+
+const HEIGHT: f32 = 24.0;
+const MARGIN: f32 = 10.0;
+
+/// Playback position in the map history, with a seekable bar at the bottom of the window.
+pub struct Timeline {
+    pub index: usize,
+    pub is_playing: bool,
+    /// Seconds between two steps during playback.
+    pub interval: f32,
+    update_timer: Instant,
+    is_seeking: bool,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            index: 0,
+            is_playing: false,
+            interval: 0.05,
+            update_timer: Instant::now(),
+            is_seeking: false,
+        }
+    }
+
+    /// Jumps to the last step, after the history was rebuilt.
+    pub fn reset(&mut self, steps: usize) {
+        self.index = steps - 1;
+        self.is_playing = false;
+    }
+
+    pub fn update(&mut self, steps: usize) {
+        let last = steps - 1;
+
+        if is_key_pressed(KeyCode::Enter) {
+            self.index = (self.index + 1) % steps;
+        }
+        if is_key_pressed(KeyCode::Space) {
+            self.is_playing = !self.is_playing;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.index = (self.index + 1).min(last);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.index = self.index.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.interval = (self.interval / 2.0).max(0.001);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.interval = (self.interval * 2.0).min(2.0);
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left) && self.contains(mouse_position()) {
+            self.is_seeking = true;
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.is_seeking = false;
+        }
+        if self.is_seeking {
+            let (x, width) = bar();
+            let ratio = ((mouse_position().0 - x) / width).clamp(0.0, 1.0);
+            self.index = (ratio * last as f32).round() as usize;
+        }
+
+        if self.is_playing && self.update_timer.elapsed().as_secs_f32() > self.interval && self.index < last {
+            self.index += 1;
+            self.update_timer = Instant::now();
+        }
+
+        self.index = self.index.min(last);
+    }
+
+    /// Whether the screen position is on the bar.
+    pub fn contains(&self, (mx, my): (f32, f32)) -> bool {
+        let (x, width) = bar();
+        let y = screen_height() - MARGIN - HEIGHT;
+        mx >= x && mx <= x + width && my >= y && my <= y + HEIGHT
+    }
+
+    pub fn draw(&self, history: &[Step]) {
+        let (x, width) = bar();
+        let y = screen_height() - MARGIN - HEIGHT;
+        let step_width = width / history.len() as f32;
+
+        draw_rectangle(x, y, width, HEIGHT, Color::from_rgba(48, 48, 48, 255));
+        for (index, step) in history.iter().enumerate() {
+            draw_rectangle(x + index as f32 * step_width, y, step_width.max(1.0), HEIGHT, color(step.kind));
+        }
+        draw_rectangle(
+            x + self.index as f32 * step_width - 1.0,
+            y - 4.0,
+            step_width.max(2.0) + 2.0,
+            HEIGHT + 8.0,
+            WHITE,
+        );
+
+        let step = &history[self.index];
+        let mut caption = format!("Step {}/{}: {:?}", self.index + 1, history.len(), step.kind);
+        if let Some((px, py)) = step.position {
+            caption += &format!(" at ({}, {})", px, py);
+        }
+        if step.kind == StepKind::Collapse {
+            caption += &format!(", {} candidates, entropy {:.2}", step.candidates, step.entropy);
+        }
+        caption += &format!(" | {:.0} steps/s", 1.0 / self.interval);
+        draw_text(&caption, x, y - 10.0, 24.0, WHITE);
+    }
+}
+
+fn bar() -> (f32, f32) {
+    (MARGIN, screen_width() - MARGIN * 2.0)
+}
+
+fn color(kind: StepKind) -> Color {
+    match kind {
+        StepKind::Tileset => GRAY,
+        StepKind::Seed => GREEN,
+        StepKind::Collapse => DARKBLUE,
+        StepKind::Contradiction => RED,
+        StepKind::Backtrack => ORANGE,
+        StepKind::Path => SKYBLUE,
+        StepKind::Prune => PURPLE,
+    }
+}
@@ -0,0 +1,92 @@
+use dungeon_crawler_map::map;
+use macroquad::prelude::*;
+
+/// Default size of a tile on screen, before zooming.
+const DISPLAY_SIZE: f32 = 64.0;
+const MIN_SIZE: f32 = 4.0;
+const MAX_SIZE: f32 = 512.0;
+
+/// Where and how large the grid is drawn on screen.
+pub struct View {
+    pub tile_size: f32,
+    /// Distance of the grid center from the window center, in pixels.
+    pub offset: Vec2,
+    /// Scale the tiles so that the whole grid fits the window.
+    pub auto_fit: bool,
+    drag_start: Option<(Vec2, Vec2)>,
+}
+
+impl View {
+    pub fn new() -> Self {
+        Self {
+            tile_size: DISPLAY_SIZE,
+            offset: Vec2::ZERO,
+            auto_fit: true,
+            drag_start: None,
+        }
+    }
+
+    /// Handles zooming with the mouse wheel and panning with the middle mouse button.
+    pub fn update(&mut self, grid_size: usize) {
+        if is_key_pressed(KeyCode::F) {
+            self.auto_fit = !self.auto_fit;
+        }
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            let mouse = Vec2::from(mouse_position());
+            let center = vec2(screen_width(), screen_height()) / 2.0;
+            let size = (self.tile_size * 1.1_f32.powf(wheel.signum())).clamp(MIN_SIZE, MAX_SIZE);
+
+            // Keep the point under the cursor in place.
+            let point = (mouse - center - self.offset) / self.tile_size;
+            self.offset = mouse - center - point * size;
+            self.tile_size = size;
+            self.auto_fit = false;
+        }
+
+        if is_mouse_button_pressed(MouseButton::Middle) {
+            self.drag_start = Some((Vec2::from(mouse_position()), self.offset));
+        }
+        if is_mouse_button_released(MouseButton::Middle) {
+            self.drag_start = None;
+        }
+        if let Some((mouse, offset)) = self.drag_start {
+            self.offset = offset + Vec2::from(mouse_position()) - mouse;
+            self.auto_fit = false;
+        }
+
+        // Recomputed every frame so that it follows window resizes.
+        if self.auto_fit {
+            self.tile_size = (screen_width().min(screen_height()) * 0.9 / grid_size.max(1) as f32).clamp(MIN_SIZE, MAX_SIZE);
+            self.offset = Vec2::ZERO;
+        }
+    }
+
+    /// Top left corner of the tile at `(x, y)` tiles away from the center.
+    pub fn get_xy(&self, x: i32, y: i32) -> (f32, f32) {
+        let size = self.tile_size;
+        let x = (screen_width() / 2.0 - size / 2.0) + self.offset.x + (x as f32 * size);
+        let y = (screen_height() / 2.0 - size / 2.0) + self.offset.y + (y as f32 * size);
+        (x, y)
+    }
+
+    /// Top left corner of the cell of a centered grid.
+    pub fn get_cell_xy(&self, (x, y): map::Position, size: usize) -> (f32, f32) {
+        let half_size = (size / 2) as i32;
+        self.get_xy(x as i32 - half_size, y as i32 - half_size)
+    }
+
+    /// Cell of a centered grid below the given screen position.
+    pub fn get_cell_at(&self, (mx, my): (f32, f32), size: usize) -> Option<map::Position> {
+        let (left, top) = self.get_cell_xy((0, 0), size);
+        let x = ((mx - left) / self.tile_size).floor();
+        let y = ((my - top) / self.tile_size).floor();
+
+        if x < 0.0 || y < 0.0 || x as usize >= size || y as usize >= size {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+}
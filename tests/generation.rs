@@ -0,0 +1,646 @@
+use dungeon_crawler_map::map::{
+    Cell, Config, Direction, Dungeon, DungeonError, Example, Floor, Grid, History, Map, Path, Position, Rect, StepKind, Variants,
+    Violation, World,
+};
+use image::{DynamicImage, GrayImage, Luma};
+use proptest::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Attempts per generated tileset before it is considered unsolvable and skipped.
+const TRIES: usize = 50;
+
+/// Tileset of 3x3 pipe tiles, each open at the center and in the middle of the sides set in `[north, east, south, west]`.
+/// A closed tile is always added so that the border can be closed off.
+fn pipe_config(tiles: &[[bool; 4]]) -> Config {
+    let tiles: Vec<[bool; 4]> = std::iter::once([false; 4]).chain(tiles.iter().copied()).collect();
+    let mut image = GrayImage::new(3 * tiles.len() as u32, 3);
+    let pixel = |open: bool| Luma([if open { 255 } else { 0 }]);
+
+    for (index, sides) in tiles.iter().enumerate() {
+        let x = 3 * index as u32;
+        image.put_pixel(x + 1, 1, pixel(sides.contains(&true)));
+        image.put_pixel(x + 1, 0, pixel(sides[0]));
+        image.put_pixel(x + 2, 1, pixel(sides[1]));
+        image.put_pixel(x + 1, 2, pixel(sides[2]));
+        image.put_pixel(x, 1, pixel(sides[3]));
+    }
+
+    Config {
+        image: Some((DynamicImage::ImageLuma8(image), 3)),
+        ..Default::default()
+    }
+}
+
+/// The dungeon tileset, configured like the viewer does.
+fn dungeon_config() -> Config {
+    let image = image::open("maps/dungeon/map.png").expect("Failed to open map image!");
+    let variants = vec![
+        Variants {
+            index: 0,
+            weight: 0.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 1,
+            entrance: true,
+            exit: true,
+            ..Default::default()
+        },
+        Variants {
+            index: 2,
+            weight: 2.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 3,
+            weight: 2.0,
+            ..Default::default()
+        },
+        Variants {
+            index: 4,
+            weight: 2.0,
+            ..Default::default()
+        },
+    ];
+
+    Config {
+        image: Some((image, 5)),
+        variants,
+        ..Default::default()
+    }
+}
+
+/// Tileset of 5x5 tiles: a dead end open to the east, a tile with two separate corridors, joining west to north and east to south,
+/// and a straight corridor from west to east.
+fn corridors_config() -> Config {
+    let mut image = GrayImage::new(15, 5);
+    let straight = (10..15).map(|x| (x, 1));
+    for (x, y) in [(2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (6, 0), (9, 3), (8, 3), (8, 4)]
+        .into_iter()
+        .chain(straight)
+    {
+        image.put_pixel(x, y, Luma([255]));
+    }
+
+    Config {
+        image: Some((DynamicImage::ImageLuma8(image), 5)),
+        ..Default::default()
+    }
+}
+
+/// Checks the finished grid of `history` against everything a generated map promises.
+fn check(map: &Map, history: &History) -> Result<(), TestCaseError> {
+    let grid = history.last().unwrap();
+    let positions = || (0..grid.tiles.len()).map(|index| (index % grid.size, index / grid.size));
+
+    for position in positions() {
+        let Some(tile) = grid.tile(&position, &map.variants) else {
+            continue;
+        };
+
+        for direction in Direction::ALL {
+            match grid.move_position(&position, direction.clone()) {
+                Some(next) => {
+                    if let Some(neighbor) = grid.tile(&next, &map.variants) {
+                        prop_assert_eq!(
+                            tile.edges.get(&direction),
+                            neighbor.edges.get(&direction.opposite()),
+                            "Edges do not match between {:?} and {:?}",
+                            position,
+                            next
+                        );
+                    }
+                }
+                None => prop_assert!(!tile.edges.is_open(&direction), "{:?} is open towards the border", position),
+            }
+        }
+    }
+
+    prop_assert_eq!(map.validate(grid), vec![]);
+
+    let entrances = grid.get_by_path(&Path::Entrance);
+    let exits = grid.get_by_path(&Path::Exit);
+    prop_assert_eq!(entrances.len(), 1);
+    prop_assert_eq!(exits.len(), 1);
+
+    let index = |(x, y): Position| y * grid.size + x;
+    let length = grid.distances(&map.variants, &exits)[index(entrances[0])];
+    prop_assert!(length.is_some(), "Exit cannot be reached from the entrance");
+    let length = length.unwrap();
+    prop_assert!(map.path_length.contains(&length), "Path length {} is out of range", length);
+
+    // The marked path is a cheapest one, paying for every tile on it but the entrance.
+    let walked: Option<usize> = grid
+        .tiles
+        .iter()
+        .flatten()
+        .filter(|cell| matches!(cell.path, Path::Track | Path::Exit))
+        .map(|cell| map.variants[cell.variant].cost)
+        .sum();
+    prop_assert_eq!(walked, Some(length));
+
+    // Pruning keeps exactly the tiles the exit can be reached from, as they were before.
+    let path = history.steps().iter().rposition(|step| step.kind == StepKind::Path).unwrap();
+    let unpruned = history.get(path);
+    let distances = unpruned.distances(&map.variants, &exits);
+
+    // Before pruning, no tile leads into an empty cell. Regenerated regions are surrounded by cells pruned earlier.
+    for position in positions() {
+        let Some(tile) = unpruned.tile(&position, &map.variants) else {
+            continue;
+        };
+        for direction in Direction::ALL.into_iter().filter(|direction| tile.edges.is_open(direction)) {
+            if let Some(next) = unpruned.move_position(&position, direction) {
+                prop_assert!(
+                    unpruned.get(&next).is_some(),
+                    "{:?} is open towards the empty {:?}",
+                    position,
+                    next
+                );
+            }
+        }
+    }
+    for position in positions() {
+        let reachable = unpruned.get(&position).is_some() && distances[index(position)].is_some();
+        let expected = if reachable { *unpruned.get(&position) } else { None };
+        prop_assert_eq!(*grid.get(&position), expected, "Pruning is wrong at {:?}", position);
+    }
+
+    Ok(())
+}
+
+/// Builds with a bounded number of attempts, since not every generated tileset can be solved.
+fn try_build(map: &mut Map, config: &Config, seed: u64) -> bool {
+    let mut rng = StdRng::seed_from_u64(seed);
+    map.load_config(config);
+
+    (0..TRIES).any(|_| {
+        map.history.clear();
+        map.try_build(&mut rng, false).is_ok()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn generated_maps_hold_invariants(
+        tiles in prop::collection::vec(any::<[bool; 4]>(), 1..4),
+        size in 2usize..8,
+        min_length in 1usize..4,
+        seed: u64,
+    ) {
+        let mut map = Map::new(size, min_length..usize::MAX);
+        if try_build(&mut map, &pipe_config(&tiles), seed) {
+            check(&map, &map.history)?;
+        }
+    }
+
+    #[test]
+    fn regenerated_regions_hold_invariants(
+        tiles in prop::collection::vec(any::<[bool; 4]>(), 1..4),
+        seed: u64,
+        (x, y, width, height) in (0usize..6, 0usize..6).prop_flat_map(|(x, y)| (Just(x), Just(y), 1..=6 - x, 1..=6 - y)),
+    ) {
+        let mut map = Map::new(6, 1..usize::MAX);
+        map.max_tries = TRIES;
+        if !try_build(&mut map, &pipe_config(&tiles), seed) {
+            return Ok(());
+        }
+
+        let grid = map.history.last().unwrap().clone();
+        let rect = Rect { x, y, width, height };
+        if let Ok((_, history)) = map.regenerate_region(&grid, &rect, &mut StdRng::seed_from_u64(seed)) {
+            check(&map, &history)?;
+
+            // Outside of the region, tiles may only be pruned, never replaced.
+            let regenerated = history.last().unwrap();
+            for (index, (old, new)) in grid.tiles.iter().zip(regenerated.tiles.iter()).enumerate() {
+                let position = (index % grid.size, index / grid.size);
+                if let (false, Some(old), Some(new)) = (rect.contains(&position), old, new) {
+                    prop_assert_eq!(old.variant, new.variant, "{:?} was replaced", position);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn regenerating_with_limits_keeps_the_outside() {
+    let config = dungeon_config();
+    let rect = Rect {
+        x: 2,
+        y: 2,
+        width: 4,
+        height: 4,
+    };
+
+    for seed in 0..8 {
+        let mut map = Map::new(8, 1..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+        let grid = map.history.last().unwrap().clone();
+
+        // Asks for one more tile than the map has, so that the region has to be repaired.
+        let count = grid.count_assets(&map.variants).get(&2).copied().unwrap_or(0);
+        map.limits.insert(2, count + 1..=usize::MAX);
+        map.max_tries = TRIES;
+        let Ok((_, history)) = map.regenerate_region(&grid, &rect, &mut StdRng::seed_from_u64(seed)) else {
+            continue;
+        };
+
+        let path = history.steps().iter().rposition(|step| step.kind == StepKind::Path).unwrap();
+        let unpruned = history.get(path);
+        for (index, (old, new)) in grid.tiles.iter().zip(unpruned.tiles.iter()).enumerate() {
+            let position = (index % grid.size, index / grid.size);
+            if !rect.contains(&position) {
+                assert_eq!(
+                    old.map(|cell| cell.variant),
+                    new.map(|cell| cell.variant),
+                    "{:?} was changed",
+                    position
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn exits_are_never_picked_at_the_entrance() {
+    let config = dungeon_config();
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        width: 8,
+        height: 8,
+    };
+
+    // An empty path is allowed, so only the exit pick keeps the entrance and exit apart.
+    for seed in 0..32 {
+        let mut map = Map::new(8, 0..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+        check(&map, &map.history).unwrap();
+
+        let grid = map.history.last().unwrap().clone();
+        let (_, history) = map.regenerate_region(&grid, &rect, &mut StdRng::seed_from_u64(seed)).unwrap();
+        check(&map, &history).unwrap();
+    }
+}
+
+#[test]
+fn dungeon_maps_hold_invariants() {
+    let config = dungeon_config();
+
+    for seed in 0..8 {
+        let mut map = Map::new(8, 1..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+        check(&map, &map.history).unwrap();
+        assert!(map.failed_attempts.iter().all(|attempt| attempt.history.is_none()));
+    }
+}
+
+#[test]
+fn open_ratio_is_measured_on_the_pruned_map() {
+    let config = Config {
+        open_ratio: Some(0.6),
+        ..dungeon_config()
+    };
+
+    for seed in 0..4 {
+        let mut map = Map::new(8, 1..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+        let grid = map.history.last().unwrap();
+        assert_eq!(map.achieved_open_ratio, Some(grid.open_ratio(&map.variants)));
+    }
+}
+
+#[test]
+fn dungeon_floors_share_stairs() {
+    let floor = |size: usize, config: Config| Floor {
+        size,
+        path_length: 1..usize::MAX,
+        config,
+    };
+
+    let mut dungeon = Dungeon::new(1);
+    dungeon
+        .build(&[floor(8, dungeon_config()), floor(10, dungeon_config())], false)
+        .unwrap();
+    let exit = dungeon.floors[0].history.last().unwrap().get_by_path(&Path::Exit);
+    assert_eq!(dungeon.floors[1].history.last().unwrap().get_by_path(&Path::Entrance), exit);
+
+    let result = dungeon.build(&[floor(8, dungeon_config()), floor(6, dungeon_config())], false);
+    assert_eq!(result, Err(DungeonError::Size(1)));
+
+    // Without any weight on the entrance asset, the stairs cannot arrive on the second floor.
+    let mut config = dungeon_config();
+    config.variants[1].weight = 0.0;
+    let result = dungeon.build(&[floor(8, dungeon_config()), floor(8, config)], false);
+    assert!(matches!(result, Err(DungeonError::Entrance(1, _))));
+}
+
+#[test]
+fn world_chunks_match_their_neighbors() {
+    let config = dungeon_config();
+    let mut map = Map::new(6, 0..usize::MAX);
+    map.load_config(&config);
+
+    let mut world = World::new(5, 6, &config);
+    let west = world.chunk((0, 0)).unwrap().clone();
+    let east = world.chunk((1, 0)).unwrap().clone();
+    for y in 0..6 {
+        let left = west.tile(&(5, y), &map.variants).unwrap();
+        let right = east.tile(&(0, y), &map.variants).unwrap();
+        assert_eq!(left.edges.east, right.edges.west, "Seam does not match in row {}", y);
+    }
+
+    // Nothing can be placed without weights, which fails instead of retrying forever.
+    let config = Config {
+        variants: (0..map.variants.len())
+            .map(|index| Variants {
+                index,
+                weight: 0.0,
+                ..Default::default()
+            })
+            .collect(),
+        ..dungeon_config()
+    };
+    let mut world = World::new(5, 6, &config);
+    world.max_tries = 10;
+    assert!(world.chunk((0, 0)).is_err());
+}
+
+#[test]
+fn learned_rules_reproduce_the_example() {
+    // A map generated from matching edges, before pruning, serves as the example.
+    let mut source = Map::new(6, 1..usize::MAX);
+    source.build(&mut StdRng::seed_from_u64(3), &dungeon_config(), false);
+    let path = source.history.steps().iter().rposition(|step| step.kind == StepKind::Path).unwrap();
+    let full = source.history.get(path);
+    let example: Example = (0..full.size)
+        .map(|y| {
+            (0..full.size)
+                .map(|x| full.tile(&(x, y), &source.variants).unwrap().key())
+                .collect()
+        })
+        .collect();
+
+    let image = image::open("maps/dungeon/map.png").expect("Failed to open map image!");
+    let config = Config::from_examples(image, 5, std::slice::from_ref(&example));
+    let rules = config.rules.clone().unwrap();
+
+    // Weights follow how often each asset appears in the example, over all of its rotations.
+    for variant in config.variants.iter() {
+        let count = example.iter().flatten().filter(|(asset, _)| *asset == variant.index).count();
+        assert_eq!(variant.weight, count as f32 / 36.0, "Wrong weight for asset {}", variant.index);
+    }
+    for cell in example.iter().flatten() {
+        assert!(
+            rules.adjacent.iter().any(|(variant, _, _)| variant == cell),
+            "{:?} is missing from the rules",
+            cell
+        );
+    }
+
+    let mut map = Map::new(6, 1..usize::MAX);
+    assert!(try_build(&mut map, &config, 0), "No map could be built from the learned rules");
+    let path = map.history.steps().iter().rposition(|step| step.kind == StepKind::Path).unwrap();
+    let grid = map.history.get(path);
+
+    // Every adjacency of the generated map also appears in the example.
+    for index in 0..grid.tiles.len() {
+        let position = (index % grid.size, index / grid.size);
+        let key = grid.tile(&position, &map.variants).unwrap().key();
+
+        for direction in Direction::ALL {
+            match grid.move_position(&position, direction.clone()) {
+                Some(next) => {
+                    let neighbor = grid.tile(&next, &map.variants).unwrap().key();
+                    assert!(
+                        rules.adjacent.contains(&(key.clone(), direction, neighbor)),
+                        "{:?} is not in the example",
+                        position
+                    );
+                }
+                None => assert!(
+                    rules.border.contains(&(key.clone(), direction)),
+                    "{:?} does not face the border in the example",
+                    position
+                ),
+            }
+        }
+    }
+    assert_eq!(map.validate(map.history.last().unwrap()), vec![]);
+}
+
+#[test]
+fn paths_avoid_impassable_tiles() {
+    let mut config = dungeon_config();
+    config.variants[3].cost = Some(3);
+    config.variants[4].cost = None;
+    let mut impassable = 0;
+
+    for seed in 0..8 {
+        let mut map = Map::new(8, 1..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+        let grid = map.history.last().unwrap();
+
+        let cost: usize = grid
+            .tiles
+            .iter()
+            .flatten()
+            .filter(|cell| matches!(cell.path, Path::Track | Path::Exit))
+            .map(|cell| map.variants[cell.variant].cost.expect("Path crosses an impassable tile"))
+            .sum();
+        assert!(map.path_length.contains(&cost));
+        check(&map, &map.history).unwrap();
+        impassable += grid.count_assets(&map.variants).get(&4).copied().unwrap_or(0);
+    }
+
+    assert!(impassable > 0, "No impassable tiles were placed");
+}
+
+#[test]
+fn pathfinding_follows_corridors_within_tiles() {
+    let derived = Config {
+        sub_tile_paths: true,
+        ..corridors_config()
+    };
+    let declared = Config {
+        variants: vec![Variants {
+            index: 1,
+            connections: Some(vec![
+                vec![Direction::West, Direction::North],
+                vec![Direction::East, Direction::South],
+            ]),
+            ..Default::default()
+        }],
+        ..corridors_config()
+    };
+
+    // Declared connections are turned along with the tile, like the image.
+    let connections = |config: &Config| {
+        let mut map = Map::new(3, 1..usize::MAX);
+        map.load_config(config);
+        map.variants
+            .iter()
+            .filter(|tile| tile.asset == 1)
+            .map(|tile| tile.connections)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(connections(&derived), connections(&declared));
+
+    for (config, joined) in [(corridors_config(), true), (derived, false), (declared, false)] {
+        let mut map = Map::new(3, 1..usize::MAX);
+        map.load_config(&config);
+        let variant = |asset, direction| {
+            let variant = map
+                .variants
+                .iter()
+                .position(|tile| tile.asset == asset && tile.direction == direction);
+            Some(Cell::new(variant.unwrap()))
+        };
+
+        // The dead end on the left enters the corridor that leads north, away from the exit on the right.
+        let mut grid = Grid::new(3);
+        grid.set(&(0, 0), variant(0, Direction::North));
+        grid.set(&(1, 0), variant(1, Direction::North));
+        grid.set(&(2, 0), variant(0, Direction::South));
+        map.prune(&mut grid, (2, 0));
+
+        assert_eq!(grid.get(&(0, 0)).is_some(), joined);
+        assert!(grid.get(&(1, 0)).is_some());
+    }
+}
+
+#[test]
+fn flow_leads_to_the_exit() {
+    // Walking onto some tiles is free, which leaves neighbors at the same distance.
+    let mut costs = dungeon_config();
+    for (variants, cost) in costs.variants.iter_mut().skip(2).zip([3, 0, 2]) {
+        variants.cost = Some(cost);
+    }
+
+    for config in [dungeon_config(), costs] {
+        for seed in 0..4 {
+            let mut map = Map::new(8, 1..usize::MAX);
+            map.build(&mut StdRng::seed_from_u64(seed), &config, false);
+            let grid = map.history.last().unwrap();
+            let distances = grid.distances_to(&map.variants, &Path::Exit);
+            let flow = grid.flow(&map.variants, &grid.get_by_path(&Path::Exit));
+            let index = |(x, y): Position| y * grid.size + x;
+            let cost = |position: &Position| grid.tile(position, &map.variants).unwrap().cost.unwrap();
+
+            // Pruning left only cells the exit can be reached from.
+            for (cell, distance) in grid.tiles.iter().zip(distances.iter()) {
+                assert_eq!(cell.is_some(), distance.is_some());
+            }
+
+            // The marked path is the cheapest one, and walks onto every tile on it but the entrance.
+            let mut position = grid.get_by_path(&Path::Entrance)[0];
+            let walked = grid.get_by_path(&Path::Track).into_iter().chain(grid.get_by_path(&Path::Exit));
+            assert_eq!(distances[index(position)], Some(walked.map(|position| cost(&position)).sum()));
+
+            while let Some(direction) = flow[index(position)].clone() {
+                let next = grid.move_position(&position, direction).unwrap();
+                assert_eq!(distances[index(position)], Some(cost(&next) + distances[index(next)].unwrap()));
+                position = next;
+            }
+            assert_eq!(grid.get(&position).unwrap().path, Path::Exit);
+        }
+    }
+}
+
+#[test]
+fn flow_takes_corridors_within_tiles_into_account() {
+    let mut map = Map::new(3, 1..usize::MAX);
+    map.load_config(&Config {
+        sub_tile_paths: true,
+        ..corridors_config()
+    });
+    let variant = |asset, from: Direction, to: Direction| {
+        let variant = map
+            .variants
+            .iter()
+            .position(|tile| tile.asset == asset && tile.edges.is_open(&from) && tile.joins(&from, &to));
+        Some(Cell::new(variant.unwrap()))
+    };
+
+    // The cell next to the exit at (1, 1) is closest to it, but its corridor from the east leads into the border.
+    // From (2, 2) the exit is reached the long way around, north through the right column.
+    let mut grid = Grid::new(3);
+    grid.set(&(1, 1), variant(2, Direction::North, Direction::South));
+    grid.set(&(1, 2), variant(1, Direction::West, Direction::North));
+    grid.set(&(2, 2), variant(1, Direction::West, Direction::North));
+    grid.set(&(2, 1), variant(2, Direction::North, Direction::South));
+    grid.set(&(2, 0), variant(1, Direction::South, Direction::West));
+    grid.set(&(1, 0), variant(1, Direction::East, Direction::South));
+
+    let distances = grid.distances(&map.variants, &[(1, 1)]);
+    let flow = grid.flow(&map.variants, &[(1, 1)]);
+    assert_eq!(distances[grid.size + 1], Some(0));
+    assert_eq!(distances[2 * grid.size + 1], Some(1));
+    assert_eq!(distances[2 * grid.size + 2], Some(4));
+    assert_eq!(flow[2 * grid.size + 2], Some(Direction::North));
+}
+
+#[test]
+fn validate_reports_corrupted_grids() {
+    let mut map = Map::new(8, 3..usize::MAX);
+    map.build(&mut StdRng::seed_from_u64(7), &dungeon_config(), false);
+    let grid = map.history.last().unwrap();
+    let entrance = grid.get_by_path(&Path::Entrance)[0];
+    let track = grid.get_by_path(&Path::Track);
+
+    let mut unknown = grid.clone();
+    unknown.get_mut(&entrance).as_mut().unwrap().variant = map.variants.len();
+    assert!(unknown.validate(&map.variants).contains(&Violation::UnknownVariant(entrance)));
+
+    let mut duplicate = grid.clone();
+    duplicate.get_mut(&track[0]).as_mut().unwrap().path = Path::Entrance;
+    assert!(matches!(
+        duplicate.validate(&map.variants)[..],
+        [Violation::DuplicateEntrance(_)]
+    ));
+
+    let mut missing = grid.clone();
+    missing.set(&entrance, None);
+    let violations = missing.validate(&map.variants);
+    assert!(violations.contains(&Violation::MissingEntrance));
+    assert!(violations.contains(&Violation::DisconnectedPath(track[0])));
+
+    // Any variant that differs from a neighbor on the side facing it.
+    let mut mismatch = grid.clone();
+    let conflicting = (0..map.variants.len())
+        .find(|variant| {
+            Direction::ALL.into_iter().any(|direction| {
+                let neighbor = grid
+                    .move_position(&track[0], direction.clone())
+                    .and_then(|next| grid.tile(&next, &map.variants));
+                neighbor.is_some_and(|neighbor| map.variants[*variant].edges.get(&direction) != neighbor.edges.get(&direction.opposite()))
+            })
+        })
+        .unwrap();
+    mismatch.get_mut(&track[0]).as_mut().unwrap().variant = conflicting;
+    assert!(mismatch
+        .validate(&map.variants)
+        .iter()
+        .any(|violation| matches!(violation, Violation::EdgeMismatch(..))));
+
+    // The track leads over a tile that cannot be walked on.
+    let mut tileset = map.variants.clone();
+    tileset[grid.get(&track[0]).unwrap().variant].cost = None;
+    assert!(grid.validate(&tileset).contains(&Violation::DisconnectedPath(track[0])));
+}
+
+#[test]
+fn builds_are_reproducible() {
+    let config = dungeon_config();
+    let build = || {
+        let mut map = Map::new(8, 1..usize::MAX);
+        map.build(&mut StdRng::seed_from_u64(42), &config, false);
+        map.history.last().unwrap().clone()
+    };
+
+    assert_eq!(build(), build());
+}